@@ -1,97 +1,330 @@
-use crate::models::ApiResponse;
-use std::error::Error;
+use crate::cache::Lookup;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::ident::{Signature, TrainIdent};
+use crate::models::{Activity, ApiResponse, TrainAnnouncement, TrainMessage, TrainStation};
+use crate::query::{Filter, Query, Request};
+use crate::state::AppState;
+use crate::upstream;
+use chrono::{DateTime, Local};
+use std::sync::Arc;
+use std::time::Duration;
 
-const ISO: &'static str = "%Y-%m-%dT%H:%M:%S%:z";
-const SINCE: chrono::TimeDelta = chrono::Duration::hours(4);
-const UNTIL: chrono::TimeDelta = chrono::Duration::hours(12);
+const ISO: &str = "%Y-%m-%dT%H:%M:%S%:z";
 
-pub async fn fetch_station(api_key: &str, code: &str) -> Result<ApiResponse, Box<dyn Error>> {
-    let data = station_query(api_key, code);
-    let response = build_request(data).send().await?;
-    let text = response.text().await?;
-    let parsed: ApiResponse = serde_json::from_str(&text)?;
-    Ok(parsed)
+/// An upstream response, possibly from the cache. `as_of` is set when
+/// Trafikverket could not be reached and the last known response was used.
+pub struct Fetched {
+    pub response: Arc<ApiResponse>,
+    pub as_of: Option<DateTime<Local>>,
 }
 
-fn station_query(api_key: &str, code: &str) -> String {
-    let now = chrono::Utc::now();
-    let since = (now - SINCE).format(ISO);
-    let until = (now + UNTIL).format(ISO);
-    format!(
-        r#"
-<REQUEST>
-  <LOGIN authenticationkey='{}' />
-  <QUERY objecttype='TrainAnnouncement' orderby='AdvertisedTimeAtLocation' sseurl='false' schemaversion='1.6'>
-    <FILTER>
-      <AND>
-        <NE name='Canceled' value='true' />
-        <EQ name='Advertised' value='true' />
-        <EQ name='ActivityType' value='Avgang' />
-        <EQ name='LocationSignature' value='{}' />
-        <GT name='AdvertisedTimeAtLocation' value='{}' />
-        <LT name='AdvertisedTimeAtLocation' value='{}' />
-      </AND>
-    </FILTER>
-    <INCLUDE>AdvertisedTrainIdent</INCLUDE>
-    <INCLUDE>AdvertisedTimeAtLocation</INCLUDE>
-    <INCLUDE>FromLocation</INCLUDE>
-    <INCLUDE>LocationSignature</INCLUDE>
-    <INCLUDE>ProductInformation</INCLUDE>
-    <INCLUDE>TimeAtLocationWithSeconds</INCLUDE>
-    <INCLUDE>ToLocation</INCLUDE>
-    <INCLUDE>ViaToLocation</INCLUDE>
-  </QUERY>
-</REQUEST>
-"#,
-        api_key, code, since, until
+impl Fetched {
+    pub fn announcements(&self) -> Vec<TrainAnnouncement> {
+        self.response
+            .response
+            .result
+            .iter()
+            .flat_map(|r| r.train_announcements.iter().cloned())
+            .collect()
+    }
+
+    /// Messages that are in effect right now, most recently started first.
+    pub fn active_messages(&self) -> Vec<TrainMessage> {
+        let now = chrono::Local::now().fixed_offset();
+        let mut messages: Vec<TrainMessage> = self
+            .response
+            .response
+            .result
+            .iter()
+            .flat_map(|r| r.train_messages.iter())
+            .filter(|message| message.is_active(now))
+            .cloned()
+            .collect();
+        messages.sort_by_key(|message| std::cmp::Reverse(message.start_date_time));
+        messages
+    }
+
+    fn current(response: Arc<ApiResponse>) -> Self {
+        Fetched {
+            response,
+            as_of: None,
+        }
+    }
+}
+
+pub async fn fetch_station(
+    state: &AppState,
+    code: &Signature,
+    activity: Activity,
+) -> Result<Fetched, AppError> {
+    let ttl = state.config.board_ttl();
+    cached(
+        state,
+        station_query(code, activity),
+        ttl,
+        announcement_window,
     )
+    .await
+}
+
+fn station_query(code: &Signature, activity: Activity) -> Query {
+    announcement_query(activity, Filter::eq("LocationSignature", code))
+}
+
+pub async fn fetch_train(state: &AppState, id: &TrainIdent) -> Result<Fetched, AppError> {
+    let ttl = state.config.train_ttl();
+    cached(state, train_query(id), ttl, announcement_window).await
+}
+
+fn train_query(id: &TrainIdent) -> Query {
+    announcement_query(Activity::Departure, Filter::eq("AdvertisedTrainIdent", id))
+}
+
+fn announcement_query(activity: Activity, selection: Filter) -> Query {
+    Query::new("TrainAnnouncement", "1.6")
+        .filter(Filter::And(vec![
+            Filter::eq("Advertised", true),
+            Filter::eq("ActivityType", activity.code()),
+            selection,
+        ]))
+        .include(&[
+            "AdvertisedTrainIdent",
+            "AdvertisedTimeAtLocation",
+            "Booking",
+            "Canceled",
+            "Deviation",
+            "EstimatedTimeAtLocation",
+            "FromLocation",
+            "LocationSignature",
+            "OtherInformation",
+            "ProductInformation",
+            "TimeAtLocationWithSeconds",
+            "ToLocation",
+            "TrackAtLocation",
+            "ViaToLocation",
+        ])
+        .order_by("AdvertisedTimeAtLocation")
+        .last_modified()
+}
+
+/// Disruptions affecting any of `locations`.
+pub async fn fetch_messages(state: &AppState, locations: &[&str]) -> Result<Fetched, AppError> {
+    let ttl = state.config.messages_ttl();
+    cached(state, message_query(locations), ttl, message_window).await
 }
 
-pub async fn fetch_train(api_key: &str, code: &str) -> Result<ApiResponse, Box<dyn Error>> {
-    let data = train_query(api_key, code);
-    let response = build_request(data).send().await?;
-    let text = response.text().await?;
-    let parsed: ApiResponse = serde_json::from_str(&text)?;
-    Ok(parsed)
+/// Disruptions anywhere in the country.
+pub async fn fetch_all_messages(state: &AppState) -> Result<Fetched, AppError> {
+    let ttl = state.config.messages_ttl();
+    cached(state, all_messages_query(), ttl, message_window).await
 }
 
-fn train_query(api_key: &str, id: &str) -> String {
+fn message_query(locations: &[&str]) -> Query {
+    all_messages_query().filter(Filter::any_of(
+        "AffectedLocation.LocationSignature",
+        locations,
+    ))
+}
+
+fn all_messages_query() -> Query {
+    Query::new("TrainMessage", "1.7")
+        .include(&[
+            "AffectedLocation",
+            "CountyNo",
+            "EventId",
+            "ExternalDescription",
+            "Header",
+            "LastUpdateDateTime",
+            "PrognosticatedEndDateTimeTrafficImpact",
+            "ReasonCodeText",
+            "StartDateTime",
+        ])
+        .order_by("StartDateTime desc")
+}
+
+/// Every location Trafikverket knows about. Not cached, since it is only
+/// called by `locations::keep_updated`.
+pub async fn fetch_stations(state: &AppState) -> Result<Vec<TrainStation>, AppError> {
+    let response = fetch(state, station_list_query()).await?;
+    Ok(response
+        .response
+        .result
+        .into_iter()
+        .flat_map(|r| r.train_stations)
+        .collect())
+}
+
+fn station_list_query() -> Query {
+    Query::new("TrainStation", "1.5")
+        .filter(Filter::eq("Deleted", false))
+        .include(&[
+            "Advertised",
+            "AdvertisedLocationName",
+            "CountyNo",
+            "Geometry.WGS84",
+            "LocationSignature",
+        ])
+}
+
+/// Kept out of the cache key so that it does not change on every request.
+fn announcement_window(config: &Config, query: Query) -> Query {
     let now = chrono::Utc::now();
-    let since = (now - SINCE).format(ISO);
-    let until = (now + UNTIL).format(ISO);
-    format!(
-        r#"
-<REQUEST>
-  <LOGIN authenticationkey='{}' />
-  <QUERY objecttype='TrainAnnouncement' orderby='AdvertisedTimeAtLocation' sseurl='false' schemaversion='1.6'>
-    <FILTER>
-      <AND>
-        <NE name='Canceled' value='true' />
-        <EQ name='Advertised' value='true' />
-        <EQ name='ActivityType' value='Avgang' />
-        <EQ name='AdvertisedTrainIdent' value='{}' />
-        <GT name='AdvertisedTimeAtLocation' value='{}' />
-        <LT name='AdvertisedTimeAtLocation' value='{}' />
-      </AND>
-    </FILTER>
-    <INCLUDE>AdvertisedTrainIdent</INCLUDE>
-    <INCLUDE>AdvertisedTimeAtLocation</INCLUDE>
-    <INCLUDE>FromLocation</INCLUDE>
-    <INCLUDE>LocationSignature</INCLUDE>
-    <INCLUDE>ProductInformation</INCLUDE>
-    <INCLUDE>TimeAtLocationWithSeconds</INCLUDE>
-    <INCLUDE>ToLocation</INCLUDE>
-    <INCLUDE>ViaToLocation</INCLUDE>
-  </QUERY>
-</REQUEST>
-"#,
-        api_key, id, since, until
-    )
+    let since = (now - config.since()).format(ISO);
+    let until = (now + config.until()).format(ISO);
+    query
+        .and(Filter::gt("AdvertisedTimeAtLocation", since))
+        .and(Filter::lt("AdvertisedTimeAtLocation", until))
+}
+
+/// Leaves out messages whose expected end has passed. Kept out of the cache
+/// key like `announcement_window`; `Fetched::active_messages` checks again,
+/// since a cached answer outlives the time it was asked at.
+fn message_window(_: &Config, query: Query) -> Query {
+    let now = chrono::Utc::now().format(ISO).to_string();
+    query.and(Filter::Or(vec![
+        Filter::gt("PrognosticatedEndDateTimeTrafficImpact", &now),
+        Filter::exists("PrognosticatedEndDateTimeTrafficImpact", false),
+    ]))
 }
 
-fn build_request(xml_data: String) -> reqwest::RequestBuilder {
-    reqwest::Client::new()
-        .post("https://api.trafikinfo.trafikverket.se/v2/data.json")
+/// Serves `query` from the cache while it is younger than `ttl`. Older
+/// entries are served for another `stale` period while a background task
+/// refreshes them, and indefinitely if Trafikverket cannot be reached.
+async fn cached(
+    state: &AppState,
+    query: Query,
+    ttl: Duration,
+    prepare: fn(&Config, Query) -> Query,
+) -> Result<Fetched, AppError> {
+    let key = query.cache_key();
+    match state.cache.get(&key, ttl, state.config.stale()) {
+        Lookup::Fresh(response) | Lookup::Stale(response) => return Ok(Fetched::current(response)),
+        Lookup::Revalidate(response) => {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let request = fetch(&state, prepare(&state.config, query));
+                match state.coalescer.run(&key, request).await {
+                    Ok(fresh) => state.cache.insert(key, fresh, state.config.keep()),
+                    Err(e) => {
+                        eprintln!("Background refresh failed: {}", e);
+                        state.cache.refresh_failed(&key);
+                    }
+                }
+            });
+            return Ok(Fetched::current(response));
+        }
+        Lookup::Missing => {}
+    }
+
+    let request = fetch(state, prepare(&state.config, query));
+    match state.coalescer.run(&key, request).await {
+        Ok(response) => {
+            state
+                .cache
+                .insert(key, response.clone(), state.config.keep());
+            Ok(Fetched::current(response))
+        }
+        Err(e) => match state.cache.last_known(&key) {
+            Some((response, fetched)) => {
+                eprintln!(
+                    "Serving data from {} after error: {}",
+                    fetched.format("%H:%M"),
+                    e
+                );
+                Ok(Fetched {
+                    response,
+                    as_of: Some(fetched),
+                })
+            }
+            None => Err(e),
+        },
+    }
+}
+
+async fn fetch(state: &AppState, query: Query) -> Result<ApiResponse, AppError> {
+    let Some(permit) = state.breaker.allow() else {
+        return Err(AppError::Unavailable);
+    };
+
+    let data = Request::new(&state.config.api_key).query(query).to_xml();
+    let mut attempt = 0;
+    let text = loop {
+        match send(state, &data).await {
+            Ok(text) => break text,
+            Err(e) if e.is_transient() && attempt < state.config.retries => {
+                eprintln!("Upstream attempt {} failed: {}", attempt + 1, e);
+                tokio::time::sleep(upstream::backoff(state.config.backoff(), attempt)).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                permit.failure(&e.to_string());
+                return Err(e);
+            }
+        }
+    };
+    permit.success();
+
+    let parsed: ApiResponse =
+        serde_json::from_str(&text).map_err(|e| AppError::Decode(e.to_string()))?;
+    match parsed.response.error() {
+        Some(e) => {
+            let error = AppError::from(e);
+            eprintln!("Trafikverket error from {}: {}", e.source, e.message);
+            Err(error)
+        }
+        None => Ok(parsed),
+    }
+}
+
+/// Network errors, timeouts and 5xx responses are errors; any other response
+/// is returned as is, since Trafikverket describes bad requests in the body.
+async fn send(state: &AppState, xml_data: &str) -> Result<String, AppError> {
+    let response = build_request(state, xml_data.to_string()).send().await?;
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(AppError::UpstreamQuota(response.text().await?));
+    }
+    if response.status().is_server_error() {
+        return Err(AppError::UpstreamHttp(response.status().as_u16()));
+    }
+    Ok(response.text().await?)
+}
+
+fn build_request(state: &AppState, xml_data: String) -> reqwest::RequestBuilder {
+    state
+        .client
+        .post(&state.config.api_url)
         .header("Content-Type", "application/xml")
+        .timeout(state.config.timeout())
         .body(xml_data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Response;
+
+    #[tokio::test]
+    async fn serves_the_last_known_response_when_upstream_fails() {
+        let state = AppState::new(Config {
+            api_key: "key".to_string(),
+            api_url: "http://127.0.0.1:9/data.json".to_string(),
+            retries: 0,
+            train_ttl_secs: 0,
+            stale_secs: 0,
+            ..Config::default()
+        });
+        let id = TrainIdent::parse("2616").unwrap();
+        let response = ApiResponse {
+            response: Response { result: Vec::new() },
+        };
+        state.cache.insert(
+            train_query(&id).cache_key(),
+            Arc::new(response),
+            state.config.keep(),
+        );
+
+        let fetched = fetch_train(&state, &id).await.unwrap();
+        assert!(fetched.as_of.is_some());
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::LazyLock;
+
+use axum::extract::Path;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+
+/// CSS and JavaScript compiled into the binary. They are served under a name
+/// containing a hash of their contents, so browsers may cache them forever
+/// and still pick up a new version as soon as it is deployed.
+struct Asset {
+    name: &'static str,
+    content_type: &'static str,
+    body: &'static str,
+}
+
+static ASSETS: [Asset; 2] = [
+    Asset {
+        name: "style.css",
+        content_type: "text/css; charset=utf-8",
+        body: include_str!("../static/style.css"),
+    },
+    Asset {
+        name: "app.js",
+        content_type: "text/javascript; charset=utf-8",
+        body: include_str!("../static/app.js"),
+    },
+];
+
+static HASHED_NAMES: LazyLock<Vec<String>> =
+    LazyLock::new(|| ASSETS.iter().map(hashed_name).collect());
+
+/// `style.css` becomes `style.3f2a9c1e5b7d4a60.css`.
+fn hashed_name(asset: &Asset) -> String {
+    let hash = fnv1a(asset.body.as_bytes());
+    match asset.name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}.{:016x}.{}", stem, hash, extension),
+        None => format!("{}.{:016x}", asset.name, hash),
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The URL templates should link to for the asset called `name`.
+pub fn url(name: &str) -> String {
+    match ASSETS.iter().position(|asset| asset.name == name) {
+        Some(index) => format!("/static/{}", HASHED_NAMES[index]),
+        None => format!("/static/{}", name),
+    }
+}
+
+pub async fn serve(Path(file): Path<String>) -> Response {
+    let Some(index) = HASHED_NAMES.iter().position(|name| *name == file) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let asset = &ASSETS[index];
+    (
+        [
+            (header::CONTENT_TYPE, asset.content_type),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        asset.body,
+    )
+        .into_response()
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+use crate::models::ApiResponse;
+
+/// Upstream responses keyed by `Query::cache_key`.
+#[derive(Default)]
+pub struct Cache {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    response: Arc<ApiResponse>,
+    fetched_at: Instant,
+    fetched_wall: DateTime<Local>,
+    refreshing: bool,
+}
+
+pub enum Lookup {
+    Fresh(Arc<ApiResponse>),
+    /// Past its TTL but still servable; the caller that gets this is the one
+    /// expected to refresh it in the background.
+    Revalidate(Arc<ApiResponse>),
+    /// Past its TTL and already being refreshed by someone else.
+    Stale(Arc<ApiResponse>),
+    Missing,
+}
+
+impl Cache {
+    pub fn get(&self, key: &str, ttl: Duration, stale: Duration) -> Lookup {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(key) else {
+            return Lookup::Missing;
+        };
+        let age = entry.fetched_at.elapsed();
+        if age < ttl {
+            Lookup::Fresh(entry.response.clone())
+        } else if age >= ttl + stale {
+            Lookup::Missing
+        } else if entry.refreshing {
+            Lookup::Stale(entry.response.clone())
+        } else {
+            entry.refreshing = true;
+            Lookup::Revalidate(entry.response.clone())
+        }
+    }
+
+    /// The most recent response regardless of age, for when upstream is down.
+    pub fn last_known(&self, key: &str) -> Option<(Arc<ApiResponse>, DateTime<Local>)> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .map(|entry| (entry.response.clone(), entry.fetched_wall))
+    }
+
+    pub fn insert(&self, key: String, response: Arc<ApiResponse>, keep: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.fetched_at.elapsed() < keep);
+        entries.insert(
+            key,
+            Entry {
+                response,
+                fetched_at: Instant::now(),
+                fetched_wall: Local::now(),
+                refreshing: false,
+            },
+        );
+    }
+
+    pub fn refresh_failed(&self, key: &str) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.refreshing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Response;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn cache_with(key: &str) -> Cache {
+        let cache = Cache::default();
+        let response = ApiResponse {
+            response: Response { result: Vec::new() },
+        };
+        cache.insert(key.to_string(), Arc::new(response), HOUR);
+        cache
+    }
+
+    #[test]
+    fn fresh_within_ttl() {
+        let cache = cache_with("q");
+        assert!(matches!(cache.get("q", HOUR, HOUR), Lookup::Fresh(_)));
+        assert!(matches!(cache.get("other", HOUR, HOUR), Lookup::Missing));
+    }
+
+    #[test]
+    fn only_the_first_caller_after_the_ttl_revalidates() {
+        let cache = cache_with("q");
+        std::thread::sleep(Duration::from_millis(20));
+        let ttl = Duration::from_millis(10);
+        assert!(matches!(cache.get("q", ttl, HOUR), Lookup::Revalidate(_)));
+        assert!(matches!(cache.get("q", ttl, HOUR), Lookup::Stale(_)));
+        assert!(matches!(cache.get("q", ttl, HOUR), Lookup::Stale(_)));
+    }
+
+    #[test]
+    fn a_failed_refresh_lets_the_next_caller_retry() {
+        let cache = cache_with("q");
+        assert!(matches!(
+            cache.get("q", Duration::ZERO, HOUR),
+            Lookup::Revalidate(_)
+        ));
+        cache.refresh_failed("q");
+        assert!(matches!(
+            cache.get("q", Duration::ZERO, HOUR),
+            Lookup::Revalidate(_)
+        ));
+    }
+
+    #[test]
+    fn a_refresh_makes_the_entry_fresh_again() {
+        let cache = cache_with("q");
+        assert!(matches!(
+            cache.get("q", Duration::ZERO, HOUR),
+            Lookup::Revalidate(_)
+        ));
+        let response = ApiResponse {
+            response: Response { result: Vec::new() },
+        };
+        cache.insert("q".to_string(), Arc::new(response), HOUR);
+        assert!(matches!(cache.get("q", HOUR, HOUR), Lookup::Fresh(_)));
+    }
+
+    #[test]
+    fn too_old_to_serve_but_still_known() {
+        let cache = cache_with("q");
+        std::thread::sleep(Duration::from_millis(20));
+        let short = Duration::from_millis(5);
+        assert!(matches!(cache.get("q", short, short), Lookup::Missing));
+        let (_, fetched) = cache.last_known("q").unwrap();
+        assert!(Local::now() - fetched < chrono::TimeDelta::seconds(5));
+        assert!(cache.last_known("other").is_none());
+    }
+
+    #[test]
+    fn inserting_drops_entries_older_than_keep() {
+        let cache = cache_with("old");
+        std::thread::sleep(Duration::from_millis(20));
+        let response = ApiResponse {
+            response: Response { result: Vec::new() },
+        };
+        cache.insert(
+            "new".to_string(),
+            Arc::new(response),
+            Duration::from_millis(10),
+        );
+        assert!(cache.last_known("old").is_none());
+        assert!(cache.last_known("new").is_some());
+    }
+}
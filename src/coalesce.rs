@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use crate::error::AppError;
+use crate::models::ApiResponse;
+
+type Flight = Arc<OnceCell<Result<Arc<ApiResponse>, AppError>>>;
+
+/// Lets concurrent callers with the same key share one upstream request.
+#[derive(Default)]
+pub struct Coalescer {
+    in_flight: Mutex<HashMap<String, Flight>>,
+    started: AtomicU64,
+    joined: AtomicU64,
+}
+
+#[derive(Serialize)]
+pub struct CoalescerStats {
+    pub upstream_calls: u64,
+    pub calls_saved: u64,
+    pub in_flight: usize,
+}
+
+impl Coalescer {
+    /// Runs `fetch` unless a request for `key` is already in flight, in which
+    /// case its result is awaited instead and `fetch` is dropped unpolled.
+    pub async fn run<F>(&self, key: &str, fetch: F) -> Result<Arc<ApiResponse>, AppError>
+    where
+        F: Future<Output = Result<ApiResponse, AppError>>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+
+        // A caller that joins a cancelled request runs its own `fetch`, so
+        // only callers that never ran one count as saved calls.
+        let mut ran_fetch = false;
+        let result = cell
+            .get_or_init(|| {
+                ran_fetch = true;
+                self.started.fetch_add(1, Ordering::Relaxed);
+                async { fetch.await.map(Arc::new) }
+            })
+            .await
+            .clone();
+        if !ran_fetch {
+            self.joined.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+            in_flight.remove(key);
+        }
+
+        result
+    }
+
+    pub fn stats(&self) -> CoalescerStats {
+        CoalescerStats {
+            upstream_calls: self.started.load(Ordering::Relaxed),
+            calls_saved: self.joined.load(Ordering::Relaxed),
+            in_flight: self.in_flight.lock().unwrap().len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancelled_leader_is_not_a_saved_call() {
+        let coalescer = Arc::new(Coalescer::default());
+        let leader = tokio::spawn({
+            let coalescer = coalescer.clone();
+            async move {
+                coalescer
+                    .run(
+                        "key",
+                        std::future::pending::<Result<ApiResponse, AppError>>(),
+                    )
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+        leader.abort();
+        let _ = leader.await;
+
+        let result = coalescer
+            .run("key", async { Err(AppError::Unavailable) })
+            .await;
+        assert!(result.is_err());
+        let stats = coalescer.stats();
+        assert_eq!(stats.upstream_calls, 2);
+        assert_eq!(stats.calls_saved, 0);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::AppError;
+use serde::Deserialize;
+use std::path::Path;
+
+const DEFAULT_PATH: &str = "trains.toml";
+
+/// Settings read from `trains.toml` (or the file named by `TRAINS_CONFIG`),
+/// with `TRAFIKVERKET_API_KEY`, `TRAFIKVERKET_API_URL` and `TRAINS_FIXTURES`
+/// taking precedence.
+///
+/// When `fixtures_dir` is set the app answers its own upstream requests from
+/// the JSON files in that directory, see `fake::data`, and `api_url` is
+/// ignored.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub api_key: String,
+    pub api_url: String,
+    pub since_hours: i64,
+    pub until_hours: i64,
+    pub fixtures_dir: Option<String>,
+    pub timeout_secs: u64,
+    pub retries: u32,
+    pub backoff_ms: u64,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown_secs: u64,
+    pub board_ttl_secs: u64,
+    pub train_ttl_secs: u64,
+    pub messages_ttl_secs: u64,
+    pub stale_secs: u64,
+    pub keep_secs: u64,
+    pub stations_cache: String,
+    pub stations_refresh_hours: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            api_key: String::new(),
+            api_url: "https://api.trafikinfo.trafikverket.se/v2/data.json".to_string(),
+            since_hours: 4,
+            until_hours: 12,
+            fixtures_dir: None,
+            timeout_secs: 10,
+            retries: 2,
+            backoff_ms: 250,
+            breaker_threshold: 5,
+            breaker_cooldown_secs: 30,
+            board_ttl_secs: 15,
+            train_ttl_secs: 15,
+            messages_ttl_secs: 60,
+            stale_secs: 60,
+            keep_secs: 3600,
+            stations_cache: "stations.json".to_string(),
+            stations_refresh_hours: 24,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Config, String> {
+        let path = std::env::var("TRAINS_CONFIG").ok();
+        let mut config = match &path {
+            Some(path) => read(Path::new(path))?,
+            None if Path::new(DEFAULT_PATH).exists() => read(Path::new(DEFAULT_PATH))?,
+            None => Config::default(),
+        };
+
+        if let Ok(key) = std::env::var("TRAFIKVERKET_API_KEY") {
+            config.api_key = key;
+        }
+        if let Ok(url) = std::env::var("TRAFIKVERKET_API_URL") {
+            config.api_url = url;
+        }
+        if let Ok(dir) = std::env::var("TRAINS_FIXTURES") {
+            config.fixtures_dir = Some(dir);
+        }
+
+        if config.fixtures_dir.is_some() && config.api_key.is_empty() {
+            config.api_key = "fake".to_string();
+        }
+        if config.api_key.is_empty() {
+            return Err(AppError::MissingKey.to_string());
+        }
+        if config.stations_refresh_hours == 0 {
+            return Err("stations_refresh_hours must be at least 1".to_string());
+        }
+        Ok(config)
+    }
+
+    /// In fixture mode, points `api_url` at the app's own fake endpoint on
+    /// `addr`, the address the server is listening on.
+    pub fn answer_from_fixtures(&mut self, addr: std::net::SocketAddr) {
+        let Some(dir) = &self.fixtures_dir else {
+            return;
+        };
+        let url = format!("http://{}/fake/data.json", addr);
+        if self.api_url != Config::default().api_url && self.api_url != url {
+            println!("Ignoring api_url {} in fixture mode", self.api_url);
+        }
+        println!("Answering upstream requests from fixtures in {}", dir);
+        self.api_url = url;
+    }
+
+    pub fn since(&self) -> chrono::TimeDelta {
+        chrono::Duration::hours(self.since_hours)
+    }
+
+    pub fn until(&self) -> chrono::TimeDelta {
+        chrono::Duration::hours(self.until_hours)
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn backoff(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.backoff_ms)
+    }
+
+    pub fn breaker_cooldown(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.breaker_cooldown_secs)
+    }
+
+    pub fn board_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.board_ttl_secs)
+    }
+
+    pub fn train_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.train_ttl_secs)
+    }
+
+    pub fn messages_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.messages_ttl_secs)
+    }
+
+    pub fn stale(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.stale_secs)
+    }
+
+    pub fn keep(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.keep_secs)
+    }
+
+    pub fn stations_refresh(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.stations_refresh_hours * 3600)
+    }
+}
+
+fn read(path: &Path) -> Result<Config, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("Could not parse {}: {}", path.display(), e))
+}
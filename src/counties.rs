@@ -0,0 +1,28 @@
+/// Swedish county (län) names by the numbers Trafikverket uses in `CountyNo`.
+pub fn name(county_no: i32) -> Option<&'static str> {
+    let name = match county_no {
+        1 | 2 => "Stockholms län",
+        3 => "Uppsala län",
+        4 => "Södermanlands län",
+        5 => "Östergötlands län",
+        6 => "Jönköpings län",
+        7 => "Kronobergs län",
+        8 => "Kalmar län",
+        9 => "Gotlands län",
+        10 => "Blekinge län",
+        12 => "Skåne län",
+        13 => "Hallands län",
+        14 => "Västra Götalands län",
+        17 => "Värmlands län",
+        18 => "Örebro län",
+        19 => "Västmanlands län",
+        20 => "Dalarnas län",
+        21 => "Gävleborgs län",
+        22 => "Västernorrlands län",
+        23 => "Jämtlands län",
+        24 => "Västerbottens län",
+        25 => "Norrbottens län",
+        _ => return None,
+    };
+    Some(name)
+}
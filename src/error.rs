@@ -0,0 +1,187 @@
+use std::fmt;
+
+use axum::extract::rejection::QueryRejection;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+use crate::formats::Format;
+use crate::models::ResultError;
+use crate::views::StationChoice;
+
+#[derive(Debug, Clone)]
+pub enum AppError {
+    MissingKey,
+    InvalidTrainIdent(String),
+    UnknownStation(String),
+    /// Several stations share the name.
+    AmbiguousStation(String, Vec<StationChoice>),
+    UnknownFormat(String),
+    /// The query string does not fit the page's parameters.
+    BadQuery(String),
+    TrainNotFound(String),
+    Timeout,
+    Network(String),
+    UpstreamHttp(u16),
+    UpstreamAuth(String),
+    UpstreamQuota(String),
+    UpstreamQuery(String),
+    Decode(String),
+    Unavailable,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::MissingKey => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::InvalidTrainIdent(_) | AppError::UnknownFormat(_) | AppError::BadQuery(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            AppError::UnknownStation(_) | AppError::TrainNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::AmbiguousStation(..) => StatusCode::MULTIPLE_CHOICES,
+            AppError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Network(_)
+            | AppError::UpstreamHttp(_)
+            | AppError::UpstreamAuth(_)
+            | AppError::UpstreamQuery(_)
+            | AppError::Decode(_) => StatusCode::BAD_GATEWAY,
+            AppError::UpstreamQuota(_) | AppError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Whether another attempt at the same request might succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AppError::Timeout | AppError::Network(_) => true,
+            AppError::UpstreamHttp(status) => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::MissingKey => write!(
+                f,
+                "No API key found: set TRAFIKVERKET_API_KEY or api_key in the config file"
+            ),
+            AppError::InvalidTrainIdent(id) => write!(f, "'{}' is not a train number", id),
+            AppError::UnknownFormat(format) => write!(
+                f,
+                "'{}' is not a format we offer: use html, json, text or csv",
+                format
+            ),
+            AppError::BadQuery(message) => f.write_str(message),
+            AppError::UnknownStation(code) => write!(f, "There is no station called '{}'", code),
+            AppError::AmbiguousStation(name, choices) => {
+                let urls: Vec<&str> = choices.iter().map(|choice| choice.url.as_str()).collect();
+                write!(
+                    f,
+                    "More than one station is called '{}': {}",
+                    name,
+                    urls.join(", ")
+                )
+            }
+            AppError::TrainNotFound(id) => write!(f, "Train {} is not running today", id),
+            AppError::Timeout => write!(f, "Trafikverket took too long to answer"),
+            AppError::Network(e) => write!(f, "Could not reach Trafikverket: {}", e),
+            AppError::UpstreamHttp(status) => {
+                write!(f, "Trafikverket answered with HTTP {}", status)
+            }
+            AppError::UpstreamAuth(message) => {
+                write!(f, "Trafikverket did not accept our API key: {}", message)
+            }
+            AppError::UpstreamQuota(message) => write!(
+                f,
+                "We have made too many requests to Trafikverket, please try again later: {}",
+                message
+            ),
+            AppError::UpstreamQuery(message) => {
+                write!(f, "Trafikverket could not answer the query: {}", message)
+            }
+            AppError::Decode(e) => write!(f, "Could not understand Trafikverket's answer: {}", e),
+            AppError::Unavailable => write!(
+                f,
+                "Trafikverket is not answering right now. Please try again in a minute."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<&ResultError> for AppError {
+    fn from(e: &ResultError) -> Self {
+        let message = e.message.clone();
+        let lower = message.to_lowercase();
+        if e.source.eq_ignore_ascii_case("Authentication") || lower.contains("authentication") {
+            AppError::UpstreamAuth(message)
+        } else if ["quota", "ratelimit", "throttling"]
+            .iter()
+            .any(|source| e.source.eq_ignore_ascii_case(source))
+            || ["quota exceeded", "rate limit exceeded", "too many requests"]
+                .iter()
+                .any(|wording| lower.contains(wording))
+        {
+            AppError::UpstreamQuota(message)
+        } else {
+            AppError::UpstreamQuery(format!("{}: {}", e.source, message))
+        }
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::BadQuery(rejection.body_text())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            AppError::Timeout
+        } else if let Some(status) = e.status() {
+            AppError::UpstreamHttp(status.as_u16())
+        } else {
+            AppError::Network(e.to_string())
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        Format::Html.error(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(source: &str, message: &str) -> AppError {
+        AppError::from(&ResultError {
+            source: source.to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn quota_errors_are_recognised() {
+        assert!(matches!(
+            classify("Quota", "Try later"),
+            AppError::UpstreamQuota(_)
+        ));
+        assert!(matches!(
+            classify("Request", "Rate limit exceeded"),
+            AppError::UpstreamQuota(_)
+        ));
+    }
+
+    #[test]
+    fn other_mentions_of_limit_are_query_errors() {
+        assert!(matches!(
+            classify("Query", "Invalid value for attribute limit"),
+            AppError::UpstreamQuery(_)
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use serde_json::{Value, json};
+
+use crate::state::AppState;
+
+/// Stand-in for the Trafikverket data endpoint. Each QUERY in the posted
+/// REQUEST is answered from `<fixtures>/<objecttype>/<value>.json`, where
+/// `<value>` is the value of one of the query's EQ or IN filters, falling
+/// back to `<fixtures>/<objecttype>.json` and then to an empty result.
+pub async fn data(State(state): State<AppState>, body: String) -> (StatusCode, Json<Value>) {
+    let Some(dir) = &state.config.fixtures_dir else {
+        return (StatusCode::NOT_FOUND, Json(json!({})));
+    };
+
+    match parse(&body) {
+        Ok(queries) => {
+            let results: Vec<Value> = queries
+                .iter()
+                .map(|query| answer(Path::new(dir), query))
+                .collect();
+            (
+                StatusCode::OK,
+                Json(json!({ "RESPONSE": { "RESULT": results } })),
+            )
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "RESPONSE": { "RESULT": [{ "ERROR": {
+                "SOURCE": "Request",
+                "MESSAGE": e,
+            } }] } })),
+        ),
+    }
+}
+
+struct FakeQuery {
+    object_type: String,
+    values: Vec<String>,
+}
+
+fn parse(xml: &str) -> Result<Vec<FakeQuery>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut queries: Vec<FakeQuery> = Vec::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(tag) | Event::Empty(tag) => match tag.name().as_ref() {
+                b"QUERY" => queries.push(FakeQuery {
+                    object_type: attribute(&tag, b"objecttype")?.unwrap_or_default(),
+                    values: Vec::new(),
+                }),
+                b"EQ" | b"IN" => {
+                    if let (Some(query), Some(value)) =
+                        (queries.last_mut(), attribute(&tag, b"value")?)
+                    {
+                        query.values.extend(value.split(',').map(|v| v.to_string()));
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(queries)
+}
+
+fn attribute(tag: &BytesStart, name: &[u8]) -> Result<Option<String>, String> {
+    for attribute in tag.attributes() {
+        let attribute = attribute.map_err(|e| e.to_string())?;
+        if attribute.key.as_ref() == name {
+            let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+            return Ok(Some(value.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+fn answer(dir: &Path, query: &FakeQuery) -> Value {
+    if !is_file_name(&query.object_type) {
+        return json!({});
+    }
+    query
+        .values
+        .iter()
+        .filter(|value| is_file_name(value))
+        .map(|value| dir.join(&query.object_type).join(format!("{}.json", value)))
+        .chain(std::iter::once(
+            dir.join(format!("{}.json", query.object_type)),
+        ))
+        .find_map(|path| read(&path))
+        .unwrap_or_else(|| json!({ query.object_type.clone(): [] }))
+}
+
+fn is_file_name(value: &str) -> bool {
+    !value.is_empty() && !value.contains(['/', '\\']) && value != "." && value != ".."
+}
+
+fn read(path: &Path) -> Option<Value> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
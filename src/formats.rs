@@ -0,0 +1,258 @@
+use axum::Json;
+use axum::http::{HeaderMap, HeaderValue, header};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::json::ErrorBody;
+use crate::models::Activity;
+use crate::views::{self, Board, Call, Disruption, Journey, StationLink};
+
+/// How the station and train pages are answered: chosen with `?format=`, or
+/// else from the `Accept` header, and HTML when neither says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Json,
+    Text,
+    Csv,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FormatQuery {
+    pub format: Option<String>,
+}
+
+impl Format {
+    pub fn negotiate(query: &FormatQuery, headers: &HeaderMap) -> Result<Format, AppError> {
+        match &query.format {
+            Some(name) => {
+                Format::from_name(name).ok_or_else(|| AppError::UnknownFormat(name.clone()))
+            }
+            None => Ok(Format::accepted(headers)),
+        }
+    }
+
+    /// The format the `Accept` header asks for, ignoring `?format=`. This is
+    /// how an unknown `?format=` is answered.
+    pub fn accepted(headers: &HeaderMap) -> Format {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .unwrap_or_default();
+        Format::from_accept(accept)
+    }
+
+    fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "html" => Some(Format::Html),
+            "json" => Some(Format::Json),
+            "text" | "txt" | "plain" => Some(Format::Text),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    /// The supported media type with the highest quality; ties go to the one
+    /// listed first. Anything unsupported falls back to HTML.
+    fn from_accept(accept: &str) -> Format {
+        let mut best: Option<(f32, Format)> = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let quality = parts
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let format = match media_type.as_str() {
+                "text/html" | "application/xhtml+xml" | "text/*" | "*/*" => Format::Html,
+                "application/json" | "application/*" => Format::Json,
+                "text/plain" => Format::Text,
+                "text/csv" => Format::Csv,
+                _ => continue,
+            };
+            if quality > 0.0 && best.is_none_or(|(q, _)| quality > q) {
+                best = Some((quality, format));
+            }
+        }
+        best.map_or(Format::Html, |(_, format)| format)
+    }
+
+    /// An error in the same format as the page would have been. Every error
+    /// response is made here, so server errors are logged here too.
+    pub fn error(self, e: AppError) -> Response {
+        let status = e.status();
+        if status.is_server_error() {
+            eprintln!("{}", e);
+        }
+        match (self, e) {
+            (Format::Html, AppError::AmbiguousStation(name, choices)) => {
+                (status, views::render_station_choices(&name, &choices)).into_response()
+            }
+            (Format::Html, e) => {
+                (status, views::render_error(status, &e.to_string())).into_response()
+            }
+            (Format::Json, e) => {
+                let body = ErrorBody {
+                    status: status.as_u16(),
+                    error: e.to_string(),
+                };
+                (status, Json(body)).into_response()
+            }
+            (Format::Text | Format::Csv, e) => (status, text(format!("{}\n", e))).into_response(),
+        }
+    }
+}
+
+/// Marks a negotiated response so caches keep one copy per `Accept` header.
+pub fn vary(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+pub fn json<T: Serialize>(body: &T) -> Response {
+    Json(body).into_response()
+}
+
+pub fn text(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+}
+
+pub fn csv(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], body).into_response()
+}
+
+pub fn board_text(board: &Board) -> String {
+    let arrivals = board.activity == Activity::Arrival;
+    let mut out = format!(
+        "{} {}\n",
+        board.station.name,
+        if arrivals { "arrivals" } else { "departures" }
+    );
+    out.push_str(&freshness_text(board.stale_as_of));
+    out.push_str(&disruptions_text(&board.disruptions));
+    out.push('\n');
+    for call in &board.calls {
+        let other_end = if arrivals { &call.from } else { &call.to };
+        out.push_str(&format!(
+            "{}  {:<6}  {:<24}  {:>5}  {:>8}  {}\n",
+            call.advertised_time.format("%H:%M"),
+            call.train_ident,
+            views::names(other_end),
+            call.track.as_deref().unwrap_or_default(),
+            status_text(call),
+            deviations(call),
+        ));
+    }
+    out
+}
+
+pub fn train_text(journey: &Journey) -> String {
+    let mut out = format!(
+        "{} {} {} - {}\n",
+        journey.product.as_deref().unwrap_or_default(),
+        journey.train_ident,
+        views::names(&journey.from),
+        views::names(&journey.to)
+    );
+    out.push_str(&freshness_text(journey.stale_as_of));
+    out.push_str(&disruptions_text(&journey.disruptions));
+    out.push('\n');
+    for call in &journey.calls {
+        out.push_str(&format!(
+            "{}  {:<24}  {:>5}  {:>8}  {}\n",
+            call.advertised_time.format("%H:%M"),
+            call.location.name,
+            call.track.as_deref().unwrap_or_default(),
+            status_text(call),
+            deviations(call),
+        ));
+    }
+    out
+}
+
+/// One row per announcement, with the same fields for boards and trains.
+pub fn announcements_csv(calls: &[Call]) -> String {
+    let mut out = String::from(
+        "train_ident,product,location,from,to,track,advertised_time,estimated_time,\
+         actual_time,delay_minutes,canceled,deviations\n",
+    );
+    for call in calls {
+        let time = |time: Option<DateTime<FixedOffset>>| {
+            time.map_or(String::new(), |time| time.to_rfc3339())
+        };
+        let fields = [
+            call.train_ident.clone(),
+            call.product.clone().unwrap_or_default(),
+            call.location.signature.clone(),
+            signatures(&call.from),
+            signatures(&call.to),
+            call.track.clone().unwrap_or_default(),
+            call.advertised_time.to_rfc3339(),
+            time(call.estimated_time),
+            time(call.actual_time),
+            call.delay
+                .map_or(String::new(), |minutes| minutes.to_string()),
+            call.canceled.to_string(),
+            deviations(call),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn freshness_text(stale_as_of: Option<DateTime<Local>>) -> String {
+    match stale_as_of {
+        Some(as_of) => format!(
+            "Trafikverket is not answering. Showing data from {}.\n",
+            as_of.format("%H:%M")
+        ),
+        None => String::new(),
+    }
+}
+
+fn disruptions_text(disruptions: &[Disruption]) -> String {
+    disruptions
+        .iter()
+        .map(|disruption| format!("! {}: {}\n", disruption.header, disruption.description))
+        .collect()
+}
+
+fn status_text(call: &Call) -> String {
+    if call.canceled {
+        "Inställt".to_string()
+    } else {
+        call.delay
+            .map_or(String::new(), |minutes| format!("{:+}", minutes))
+    }
+}
+
+fn deviations(call: &Call) -> String {
+    call.deviations
+        .iter()
+        .map(|notice| notice.description.as_str())
+        .collect::<Vec<&str>>()
+        .join("; ")
+}
+
+fn signatures(stations: &[StationLink]) -> String {
+    stations
+        .iter()
+        .map(|station| station.signature.as_str())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+
+use crate::locations::Stations;
+
+/// A location signature known to the station table, e.g. `Sk` or `Tul`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Signature(String);
+
+impl Signature {
+    pub fn parse(code: &str, stations: &Stations) -> Option<Signature> {
+        if stations.is_known(code) {
+            Some(Signature(code.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An advertised train number: one to six digits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TrainIdent(String);
+
+impl TrainIdent {
+    pub fn parse(id: &str) -> Option<TrainIdent> {
+        if (1..=6).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_digit()) {
+            Some(TrainIdent(id.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for TrainIdent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_train_numbers() {
+        assert!(TrainIdent::parse("1").is_some());
+        assert!(TrainIdent::parse("123456").is_some());
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for id in ["1'2", "", "1234567", "12a", "-1"] {
+            assert!(TrainIdent::parse(id).is_none(), "{:?} was accepted", id);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! The `/api/v1` JSON schema. These types are a stable contract for scripts
+//! and dashboards: fields may be added, but none are renamed or removed
+//! within `v1`. Times are ISO 8601 with the offset Trafikverket reports.
+
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::error::AppError;
+use crate::formats::Format;
+use crate::models::{self, Activity};
+use crate::views::{self, Board, Call, Journey, StationLink};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Trains API",
+        description = "Departures, arrivals and journeys from Trafikverket's open data."
+    ),
+    tags((name = "v1", description = "Version 1 of the JSON API")),
+    paths(
+        crate::routes::station_json,
+        crate::routes::arrivals_json,
+        crate::routes::train_json,
+    )
+)]
+pub struct ApiDoc;
+
+impl ApiDoc {
+    /// The generated document, without the empty license taken from
+    /// `Cargo.toml`.
+    pub fn spec() -> utoipa::openapi::OpenApi {
+        let mut spec = ApiDoc::openapi();
+        spec.info.license = None;
+        spec
+    }
+}
+
+/// Departures or arrivals at one station.
+#[derive(Serialize, ToSchema)]
+pub struct StationBoard {
+    pub station: Location,
+    /// `departure` or `arrival`.
+    #[schema(example = "departure")]
+    pub activity: &'static str,
+    #[serde(flatten)]
+    pub freshness: Freshness,
+    /// Disruptions currently affecting the station.
+    pub disruptions: Vec<Disruption>,
+    pub announcements: Vec<Announcement>,
+}
+
+/// Every stop of one train, in timetable order.
+#[derive(Serialize, ToSchema)]
+pub struct Train {
+    pub train_ident: String,
+    pub product: Option<String>,
+    pub from: Vec<Location>,
+    pub via: Vec<Location>,
+    pub to: Vec<Location>,
+    #[serde(flatten)]
+    pub freshness: Freshness,
+    /// Disruptions currently affecting any of the train's stops.
+    pub disruptions: Vec<Disruption>,
+    pub stops: Vec<Announcement>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Freshness {
+    /// When Trafikverket last changed the data.
+    pub last_modified: Option<DateTime<FixedOffset>>,
+    /// Only set when Trafikverket could not be reached and older data is
+    /// served instead: the time that data was fetched.
+    pub stale_as_of: Option<DateTime<Local>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Location {
+    pub signature: String,
+    pub name: String,
+}
+
+/// One train calling at one station.
+#[derive(Serialize, ToSchema)]
+pub struct Announcement {
+    pub train_ident: String,
+    pub product: Option<String>,
+    pub location: Location,
+    pub from: Vec<Location>,
+    pub to: Vec<Location>,
+    pub advertised_time: DateTime<FixedOffset>,
+    pub estimated_time: Option<DateTime<FixedOffset>>,
+    pub actual_time: Option<DateTime<FixedOffset>>,
+    /// Whole minutes late (negative when early), from the actual time or
+    /// else the estimated time; null when neither is known.
+    pub delay_minutes: Option<i64>,
+    pub canceled: bool,
+    pub track: Option<String>,
+    /// The track the train was previously announced at, if it has changed.
+    pub planned_track: Option<String>,
+    pub deviations: Vec<Notice>,
+    pub other_information: Vec<Notice>,
+    pub booking: Vec<Notice>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Notice {
+    pub code: String,
+    pub description: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Disruption {
+    pub header: String,
+    pub description: String,
+    pub start: DateTime<FixedOffset>,
+    pub expected_end: Option<DateTime<FixedOffset>>,
+    pub last_updated: Option<DateTime<FixedOffset>>,
+    pub stations: Vec<Location>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub error: String,
+}
+
+/// An `AppError` answered with an `ErrorBody` instead of an HTML page.
+pub struct ApiError(pub AppError);
+
+impl From<AppError> for ApiError {
+    fn from(e: AppError) -> Self {
+        ApiError(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        Format::Json.error(self.0)
+    }
+}
+
+pub fn station_board(board: &Board) -> StationBoard {
+    StationBoard {
+        station: location(&board.station),
+        activity: match board.activity {
+            Activity::Departure => "departure",
+            Activity::Arrival => "arrival",
+        },
+        freshness: Freshness {
+            last_modified: board.last_modified,
+            stale_as_of: board.stale_as_of,
+        },
+        disruptions: board.disruptions.iter().map(disruption).collect(),
+        announcements: board.calls.iter().map(announcement).collect(),
+    }
+}
+
+pub fn train(journey: &Journey) -> Train {
+    Train {
+        train_ident: journey.train_ident.clone(),
+        product: journey.product.clone(),
+        from: journey.from.iter().map(location).collect(),
+        via: journey.via.iter().map(location).collect(),
+        to: journey.to.iter().map(location).collect(),
+        freshness: Freshness {
+            last_modified: journey.last_modified,
+            stale_as_of: journey.stale_as_of,
+        },
+        disruptions: journey.disruptions.iter().map(disruption).collect(),
+        stops: journey.calls.iter().map(announcement).collect(),
+    }
+}
+
+fn announcement(call: &Call) -> Announcement {
+    Announcement {
+        train_ident: call.train_ident.clone(),
+        product: call.product.clone(),
+        location: location(&call.location),
+        from: call.from.iter().map(location).collect(),
+        to: call.to.iter().map(location).collect(),
+        advertised_time: call.advertised_time,
+        estimated_time: call.estimated_time,
+        actual_time: call.actual_time,
+        delay_minutes: call.delay,
+        canceled: call.canceled,
+        track: call.track.clone(),
+        planned_track: call.planned_track.clone(),
+        deviations: notices(&call.deviations),
+        other_information: notices(&call.other_information),
+        booking: notices(&call.booking),
+    }
+}
+
+fn disruption(disruption: &views::Disruption) -> Disruption {
+    Disruption {
+        header: disruption.header.clone(),
+        description: disruption.description.clone(),
+        start: disruption.start,
+        expected_end: disruption.expected_end,
+        last_updated: disruption.last_updated,
+        stations: disruption.stations.iter().map(location).collect(),
+    }
+}
+
+fn location(station: &StationLink) -> Location {
+    Location {
+        signature: station.signature.clone(),
+        name: station.name.clone(),
+    }
+}
+
+fn notices(notices: &[models::Notice]) -> Vec<Notice> {
+    notices
+        .iter()
+        .map(|notice| Notice {
+            code: notice.code.clone(),
+            description: notice.description.clone(),
+        })
+        .collect()
+}
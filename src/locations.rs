@@ -1,11 +1,221 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
 use phf::phf_map;
+use serde::{Deserialize, Serialize};
+
+use crate::api;
+use crate::models::TrainStation;
+use crate::search;
+use crate::state::AppState;
+
+/// A location as loaded from Trafikverket's `TrainStation` object type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Station {
+    pub signature: String,
+    pub name: String,
+    pub advertised: bool,
+    pub county_no: Vec<i32>,
+    #[serde(default)]
+    pub position: Option<Position>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Position {
+    /// Great-circle distance in kilometres.
+    pub fn distance_km(&self, other: &Position) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
 
-pub fn name(signature: &str) -> String {
-    LOCATIONS
-        .get(signature)
-        .copied()
-        .unwrap_or(signature)
-        .to_string()
+/// The station table loaded at runtime, shared through `AppState`.
+/// `LOCATIONS` fills in every signature missing here, so a partial table,
+/// or none before the first successful load, still knows every station.
+#[derive(Default)]
+pub struct Stations {
+    table: HashMap<String, Station>,
+    /// Signatures by normalized name, rebuilt along with `table`.
+    names: HashMap<String, Vec<String>>,
+}
+
+static LOCATION_NAMES: LazyLock<HashMap<String, Vec<String>>> = LazyLock::new(|| {
+    index_names(
+        LOCATIONS
+            .entries()
+            .map(|(signature, name)| (*signature, *name)),
+    )
+});
+
+impl Stations {
+    pub fn name(&self, signature: &str) -> String {
+        if let Some(station) = self.lookup(signature) {
+            return station.name.clone();
+        }
+        LOCATIONS
+            .get(signature)
+            .copied()
+            .unwrap_or(signature)
+            .to_string()
+    }
+
+    pub fn lookup(&self, signature: &str) -> Option<&Station> {
+        self.table.get(signature)
+    }
+
+    /// Signatures of the stations called `name`, compared the way search
+    /// does, so "Stockholm C" and "stockholm-c" both give `Cst`. More than
+    /// one signature means the name is ambiguous.
+    pub fn signatures_named(&self, name: &str) -> Vec<String> {
+        let key = search::normalize(name);
+        let loaded = self.names.get(&key).into_iter().flatten();
+        let fallback = LOCATION_NAMES
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .filter(|signature| !self.table.contains_key(*signature));
+        let mut signatures: Vec<String> = loaded.chain(fallback).cloned().collect();
+        signatures.sort();
+        signatures
+    }
+
+    pub fn is_known(&self, signature: &str) -> bool {
+        self.lookup(signature).is_some() || LOCATIONS.contains_key(signature)
+    }
+
+    /// The `limit` advertised stations closest to `position`, nearest first.
+    /// Only the runtime table has positions, so `LOCATIONS` cannot help here.
+    pub fn nearest(&self, position: &Position, limit: usize) -> Vec<(Station, f64)> {
+        let mut nearby: Vec<(Station, f64)> = self
+            .table
+            .values()
+            .filter(|station| station.advertised)
+            .filter_map(|station| {
+                let distance = station.position?.distance_km(position);
+                Some((station.clone(), distance))
+            })
+            .collect();
+        nearby.sort_by(|a, b| a.1.total_cmp(&b.1));
+        nearby.truncate(limit);
+        nearby
+    }
+
+    /// Signature and name of every passenger station: the advertised ones
+    /// in the runtime table, and those in `LOCATIONS` it does not have.
+    pub fn all(&self) -> Vec<(String, String)> {
+        let loaded = self
+            .table
+            .values()
+            .filter(|station| station.advertised)
+            .map(|station| (station.signature.clone(), station.name.clone()));
+        let fallback = LOCATIONS
+            .entries()
+            .filter(|(signature, _)| !self.table.contains_key(**signature))
+            .map(|(signature, name)| (signature.to_string(), name.to_string()));
+        loaded.chain(fallback).collect()
+    }
+
+    fn replace(&mut self, stations: Vec<Station>) {
+        let names = index_names(
+            stations
+                .iter()
+                .map(|station| (station.signature.as_str(), station.name.as_str())),
+        );
+        let table = stations
+            .into_iter()
+            .map(|station| (station.signature.clone(), station))
+            .collect();
+        self.table = table;
+        self.names = names;
+    }
+
+    /// Reads the table saved by a previous run, if there is one.
+    pub fn load_cache(&mut self, path: &Path) {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return;
+        };
+        match serde_json::from_str::<Vec<Station>>(&text) {
+            Ok(stations) => {
+                println!("Loaded {} stations from {}", stations.len(), path.display());
+                self.replace(stations);
+            }
+            Err(e) => eprintln!("Ignoring {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn index_names<'a>(
+    entries: impl Iterator<Item = (&'a str, &'a str)>,
+) -> HashMap<String, Vec<String>> {
+    let mut index: HashMap<String, Vec<String>> = HashMap::new();
+    for (signature, name) in entries {
+        index
+            .entry(search::normalize(name))
+            .or_default()
+            .push(signature.to_string());
+    }
+    for signatures in index.values_mut() {
+        signatures.sort();
+    }
+    index
+}
+
+fn save_cache(path: &Path, stations: &[Station]) {
+    let result = serde_json::to_string(stations)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Could not save stations to {}: {}", path.display(), e);
+    }
+}
+
+/// Fetches `TrainStation` now and then every `stations_refresh_hours`,
+/// replacing the table and the cache file on success. In fixture mode the
+/// table comes from the fixtures and the cache file is left alone.
+pub async fn keep_updated(state: AppState) {
+    let path = Path::new(&state.config.stations_cache).to_path_buf();
+    let fixtures = state.config.fixtures_dir.is_some();
+    loop {
+        match api::fetch_stations(&state).await {
+            Ok(train_stations) if !train_stations.is_empty() => {
+                let stations: Vec<Station> = train_stations.iter().map(station).collect();
+                if fixtures {
+                    println!("Loaded {} stations from the fixtures", stations.len());
+                } else {
+                    println!("Loaded {} stations from Trafikverket", stations.len());
+                    save_cache(&path, &stations);
+                }
+                state.stations.write().unwrap().replace(stations);
+            }
+            Ok(_) => eprintln!("Trafikverket returned no stations, keeping the old table"),
+            Err(e) => eprintln!("Could not load stations: {}", e),
+        }
+        tokio::time::sleep(state.config.stations_refresh()).await;
+    }
+}
+
+fn station(train_station: &TrainStation) -> Station {
+    Station {
+        signature: train_station.location_signature.clone(),
+        name: train_station.advertised_location_name.clone(),
+        advertised: train_station.advertised,
+        county_no: train_station.county_no.clone(),
+        position: train_station
+            .geometry
+            .as_ref()
+            .and_then(|geometry| geometry.lat_lon())
+            .map(|(lat, lon)| Position { lat, lon }),
+    }
 }
 
 static LOCATIONS: phf::Map<&'static str, &'static str> = phf_map! {
@@ -1552,3 +1762,35 @@ static LOCATIONS: phf::Map<&'static str, &'static str> = phf_map! {
     "Öä" => "Öreälv",
     "Öäg" => "Österäng"
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial_table() -> Stations {
+        let mut stations = Stations::default();
+        stations.replace(vec![Station {
+            signature: "Sk".to_string(),
+            name: "Skövde C".to_string(),
+            advertised: true,
+            county_no: vec![14],
+            position: None,
+        }]);
+        stations
+    }
+
+    #[test]
+    fn a_partial_table_falls_back_to_the_compiled_in_stations() {
+        let stations = partial_table();
+        assert_eq!(stations.name("Sk"), "Skövde C");
+        assert_eq!(stations.name("Cst"), "Stockholm C");
+        assert!(stations.is_known("Cst"));
+        assert_eq!(stations.signatures_named("stockholm c"), vec!["Cst"]);
+        assert_eq!(stations.signatures_named("skövde c"), vec!["Sk"]);
+
+        let all = stations.all();
+        assert_eq!(all.len(), LOCATIONS.len());
+        assert!(all.contains(&("Sk".to_string(), "Skövde C".to_string())));
+        assert!(all.contains(&("Cst".to_string(), "Stockholm C".to_string())));
+    }
+}
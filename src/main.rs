@@ -1,21 +1,158 @@
 mod api;
+mod assets;
+mod cache;
+mod coalesce;
+mod config;
+mod counties;
+mod error;
+mod fake;
+mod formats;
+mod ident;
+mod json;
 mod locations;
 mod models;
+mod query;
 mod routes;
+mod search;
+mod state;
+mod tracks;
+mod upstream;
 mod views;
 
+use state::AppState;
+
 #[tokio::main]
 async fn main() {
+    let mut config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 3000));
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    config.answer_from_fixtures(addr);
     println!("Server running at http://{}", addr);
 
-    axum::serve(
-        tokio::net::TcpListener::bind(addr).await.unwrap(),
-        axum::Router::new()
-            .route("/", axum::routing::get(routes::stations))
-            .route("/station/:code", axum::routing::get(routes::station))
-            .route("/train/:id", axum::routing::get(routes::train)),
-    )
-    .await
-    .unwrap();
+    let state = AppState::new(config);
+    if state.config.fixtures_dir.is_none() {
+        let path = std::path::Path::new(&state.config.stations_cache);
+        state.stations.write().unwrap().load_cache(path);
+    }
+    tokio::spawn(locations::keep_updated(state.clone()));
+
+    axum::serve(listener, app(state)).await.unwrap();
+}
+
+fn app(state: AppState) -> axum::Router {
+    axum::Router::new()
+        .route("/", axum::routing::get(routes::stations))
+        .route("/station/:code", axum::routing::get(routes::station))
+        .route(
+            "/station/:code/arrivals",
+            axum::routing::get(routes::arrivals),
+        )
+        .route("/train/:id", axum::routing::get(routes::train))
+        .route("/api/openapi.json", axum::routing::get(routes::openapi))
+        .route("/api/docs", axum::routing::get(routes::api_docs))
+        .route(
+            &routes::route_path(routes::STATION_JSON_PATH),
+            axum::routing::get(routes::station_json),
+        )
+        .route(
+            &routes::route_path(routes::ARRIVALS_JSON_PATH),
+            axum::routing::get(routes::arrivals_json),
+        )
+        .route(
+            &routes::route_path(routes::TRAIN_JSON_PATH),
+            axum::routing::get(routes::train_json),
+        )
+        .route("/disruptions", axum::routing::get(routes::disruptions))
+        .route("/nearby", axum::routing::get(routes::nearby))
+        .route("/search", axum::routing::get(routes::search))
+        .route("/search.json", axum::routing::get(routes::search_json))
+        .route(
+            "/admin/upstream",
+            axum::routing::get(routes::upstream_status),
+        )
+        .route("/static/:file", axum::routing::get(assets::serve))
+        .route("/fake/data.json", axum::routing::post(fake::data))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves the router on a free port with upstream requests answered by
+    /// its own fake endpoint from the checked-in fixtures.
+    async fn serve_fixtures() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut config = config::Config {
+            api_key: "fake".to_string(),
+            fixtures_dir: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures").to_string()),
+            ..config::Config::default()
+        };
+        config.answer_from_fixtures(addr);
+        tokio::spawn(axum::serve(listener, app(AppState::new(config))).into_future());
+        format!("http://{}", addr)
+    }
+
+    async fn get(url: &str) -> (reqwest::StatusCode, String) {
+        let response = reqwest::get(url).await.unwrap();
+        (response.status(), response.text().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn station_page_from_fixtures() {
+        let base = serve_fixtures().await;
+        let (status, body) = get(&format!("{}/station/Sk", base)).await;
+        assert_eq!(status, 200);
+        assert!(body.contains("Skövde"));
+        assert!(body.contains("/train/2616"));
+    }
+
+    #[tokio::test]
+    async fn train_page_from_fixtures() {
+        let base = serve_fixtures().await;
+        let (status, body) = get(&format!("{}/train/2616", base)).await;
+        assert_eq!(status, 200);
+        assert!(body.contains("2616"));
+        assert!(body.contains("Göteborg"));
+        assert!(body.contains("Stockholm C"));
+    }
+
+    #[tokio::test]
+    async fn bad_queries_are_answered_as_accepted() {
+        let base = serve_fixtures().await;
+        for query in ["format=xml", "hide_canceled=yes"] {
+            let response = reqwest::Client::new()
+                .get(format!("{}/station/Sk?{}", base, query))
+                .header("accept", "application/json")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400, "{}", query);
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(body["status"], 400, "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn documented_paths_are_served() {
+        let base = serve_fixtures().await;
+        let spec = json::ApiDoc::spec();
+        assert!(!spec.paths.paths.is_empty());
+        for path in spec.paths.paths.keys() {
+            let url = format!("{}{}", base, path)
+                .replace("{code}", "Sk")
+                .replace("{id}", "2616");
+            let (status, _) = get(&url).await;
+            assert_eq!(status, 200, "{}", path);
+        }
+    }
 }
@@ -1,25 +1,88 @@
 use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiResponse {
     #[serde(rename = "RESPONSE")]
     pub response: Response,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Response {
     #[serde(rename = "RESULT")]
     pub result: Vec<Result>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Result {
-    #[serde(rename = "TrainAnnouncement")]
+    #[serde(rename = "TrainAnnouncement", default)]
     pub train_announcements: Vec<TrainAnnouncement>,
+
+    #[serde(rename = "TrainMessage", default)]
+    pub train_messages: Vec<TrainMessage>,
+
+    #[serde(rename = "TrainStation", default)]
+    pub train_stations: Vec<TrainStation>,
+
+    #[serde(rename = "ERROR")]
+    pub error: Option<ResultError>,
+
+    #[serde(rename = "INFO")]
+    pub info: Option<Info>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultError {
+    #[serde(rename = "SOURCE", default)]
+    pub source: String,
+
+    #[serde(rename = "MESSAGE", default)]
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Info {
+    #[serde(rename = "LASTMODIFIED")]
+    pub last_modified: Option<LastModified>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastModified {
+    #[serde(rename = "_attr_datetime")]
+    pub datetime: DateTime<FixedOffset>,
+}
+
+impl Response {
+    pub fn error(&self) -> Option<&ResultError> {
+        self.result.iter().find_map(|r| r.error.as_ref())
+    }
+
+    pub fn last_modified(&self) -> Option<DateTime<FixedOffset>> {
+        self.result
+            .iter()
+            .filter_map(|r| r.info.as_ref()?.last_modified.as_ref())
+            .map(|l| l.datetime)
+            .max()
+    }
+}
+
+/// The `ActivityType` of an announcement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Departure,
+    Arrival,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Activity {
+    pub fn code(self) -> &'static str {
+        match self {
+            Activity::Departure => "Avgang",
+            Activity::Arrival => "Ankomst",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainAnnouncement {
     #[serde(rename = "AdvertisedTrainIdent")]
     pub advertised_train_ident: String,
@@ -30,9 +93,18 @@ pub struct TrainAnnouncement {
     #[serde(rename = "LocationSignature")]
     pub location_signature: String,
 
+    #[serde(rename = "Canceled", default)]
+    pub canceled: bool,
+
+    #[serde(rename = "EstimatedTimeAtLocation")]
+    pub estimated_time_at_location: Option<DateTime<FixedOffset>>,
+
     #[serde(rename = "TimeAtLocationWithSeconds")]
     pub time_at_location_with_seconds: Option<DateTime<FixedOffset>>,
 
+    #[serde(rename = "TrackAtLocation")]
+    pub track_at_location: Option<String>,
+
     #[serde(rename = "ToLocation", default)]
     pub to_location: Vec<TrainLocation>,
 
@@ -44,9 +116,18 @@ pub struct TrainAnnouncement {
 
     #[serde(rename = "ProductInformation", default)]
     pub product_information: Vec<ProductInformation>,
+
+    #[serde(rename = "Deviation", default)]
+    pub deviation: Vec<Notice>,
+
+    #[serde(rename = "OtherInformation", default)]
+    pub other_information: Vec<Notice>,
+
+    #[serde(rename = "Booking", default)]
+    pub booking: Vec<Notice>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProductInformation {
     #[serde(rename = "Code")]
     pub code: String,
@@ -55,7 +136,18 @@ pub struct ProductInformation {
     pub description: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// An entry in `Deviation`, `OtherInformation` or `Booking`,
+/// e.g. "Kort tåg" or "Ersätts av buss".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notice {
+    #[serde(rename = "Code", default)]
+    pub code: String,
+
+    #[serde(rename = "Description")]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainLocation {
     #[serde(rename = "LocationName")]
     pub location_name: String,
@@ -66,3 +158,95 @@ pub struct TrainLocation {
     #[serde(rename = "Order")]
     pub order: i32,
 }
+
+/// A traffic disruption, such as a signal failure, affecting some locations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainMessage {
+    #[serde(rename = "EventId")]
+    pub event_id: String,
+
+    #[serde(rename = "Header", default)]
+    pub header: String,
+
+    #[serde(rename = "ExternalDescription", default)]
+    pub external_description: String,
+
+    #[serde(rename = "ReasonCodeText")]
+    pub reason_code_text: Option<String>,
+
+    #[serde(rename = "StartDateTime")]
+    pub start_date_time: DateTime<FixedOffset>,
+
+    #[serde(rename = "PrognosticatedEndDateTimeTrafficImpact")]
+    pub prognosticated_end_date_time_traffic_impact: Option<DateTime<FixedOffset>>,
+
+    #[serde(rename = "LastUpdateDateTime")]
+    pub last_update_date_time: Option<DateTime<FixedOffset>>,
+
+    #[serde(rename = "AffectedLocation", default)]
+    pub affected_location: Vec<AffectedLocation>,
+
+    #[serde(rename = "CountyNo", default)]
+    pub county_no: Vec<i32>,
+}
+
+impl TrainMessage {
+    /// Started, and not yet past its expected end if one is given.
+    pub fn is_active(&self, now: DateTime<FixedOffset>) -> bool {
+        self.start_date_time <= now
+            && self
+                .prognosticated_end_date_time_traffic_impact
+                .is_none_or(|end| end > now)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AffectedLocation {
+    #[serde(rename = "LocationSignature")]
+    pub location_signature: String,
+
+    #[serde(rename = "ShouldBeTrafficInformed", default)]
+    pub should_be_traffic_informed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainStation {
+    #[serde(rename = "LocationSignature")]
+    pub location_signature: String,
+
+    #[serde(rename = "AdvertisedLocationName")]
+    pub advertised_location_name: String,
+
+    #[serde(rename = "Advertised", default)]
+    pub advertised: bool,
+
+    #[serde(rename = "CountyNo", default)]
+    pub county_no: Vec<i32>,
+
+    #[serde(rename = "Geometry")]
+    pub geometry: Option<Geometry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Geometry {
+    /// A WKT point, e.g. `POINT (18.058 59.330)`, longitude first.
+    #[serde(rename = "WGS84")]
+    pub wgs84: String,
+}
+
+impl Geometry {
+    /// Latitude and longitude.
+    pub fn lat_lon(&self) -> Option<(f64, f64)> {
+        let inner = self
+            .wgs84
+            .trim()
+            .strip_prefix("POINT")?
+            .trim()
+            .strip_prefix('(')?
+            .strip_suffix(')')?;
+        let mut parts = inner.split_whitespace();
+        let lon = parts.next()?.parse().ok()?;
+        let lat = parts.next()?.parse().ok()?;
+        Some((lat, lon))
+    }
+}
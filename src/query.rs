@@ -0,0 +1,261 @@
+use std::fmt::Write;
+
+pub struct Request {
+    api_key: String,
+    queries: Vec<Query>,
+}
+
+impl Request {
+    pub fn new(api_key: &str) -> Self {
+        Request {
+            api_key: api_key.to_string(),
+            queries: Vec::new(),
+        }
+    }
+
+    pub fn query(mut self, query: Query) -> Self {
+        self.queries.push(query);
+        self
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<REQUEST>");
+        write!(
+            xml,
+            "<LOGIN authenticationkey='{}' />",
+            escape(&self.api_key)
+        )
+        .unwrap();
+        for query in &self.queries {
+            query.write_xml(&mut xml);
+        }
+        xml.push_str("</REQUEST>");
+        xml
+    }
+}
+
+#[derive(Clone)]
+pub struct Query {
+    object_type: String,
+    schema_version: String,
+    filter: Option<Filter>,
+    include: Vec<String>,
+    order_by: Option<String>,
+    last_modified: bool,
+}
+
+impl Query {
+    pub fn new(object_type: &str, schema_version: &str) -> Self {
+        Query {
+            object_type: object_type.to_string(),
+            schema_version: schema_version.to_string(),
+            filter: None,
+            include: Vec::new(),
+            order_by: None,
+            last_modified: false,
+        }
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Adds `filter` to the query's top-level AND, creating it if needed.
+    pub fn and(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter.take() {
+            None => filter,
+            Some(Filter::And(mut filters)) => {
+                filters.push(filter);
+                Filter::And(filters)
+            }
+            Some(other) => Filter::And(vec![other, filter]),
+        });
+        self
+    }
+
+    pub fn include(mut self, fields: &[&str]) -> Self {
+        self.include.extend(fields.iter().map(|f| f.to_string()));
+        self
+    }
+
+    pub fn order_by(mut self, field: &str) -> Self {
+        self.order_by = Some(field.to_string());
+        self
+    }
+
+    /// Asks Trafikverket to report when the result last changed.
+    pub fn last_modified(mut self) -> Self {
+        self.last_modified = true;
+        self
+    }
+
+    /// The query without credentials, usable as a key for identical queries.
+    pub fn cache_key(&self) -> String {
+        let mut xml = String::new();
+        self.write_xml(&mut xml);
+        xml
+    }
+
+    fn write_xml(&self, xml: &mut String) {
+        write!(
+            xml,
+            "<QUERY objecttype='{}' schemaversion='{}'",
+            escape(&self.object_type),
+            escape(&self.schema_version)
+        )
+        .unwrap();
+        if let Some(order_by) = &self.order_by {
+            write!(xml, " orderby='{}'", escape(order_by)).unwrap();
+        }
+        if self.last_modified {
+            xml.push_str(" lastmodified='true'");
+        }
+        xml.push('>');
+        if let Some(filter) = &self.filter {
+            xml.push_str("<FILTER>");
+            filter.write_xml(xml);
+            xml.push_str("</FILTER>");
+        }
+        for field in &self.include {
+            write!(xml, "<INCLUDE>{}</INCLUDE>", escape(field)).unwrap();
+        }
+        xml.push_str("</QUERY>");
+    }
+}
+
+#[derive(Clone)]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Eq(String, String),
+    Gt(String, String),
+    Lt(String, String),
+    In(String, Vec<String>),
+    Exists(String, bool),
+}
+
+impl Filter {
+    pub fn eq(name: &str, value: impl ToString) -> Self {
+        Filter::Eq(name.to_string(), value.to_string())
+    }
+
+    pub fn gt(name: &str, value: impl ToString) -> Self {
+        Filter::Gt(name.to_string(), value.to_string())
+    }
+
+    pub fn lt(name: &str, value: impl ToString) -> Self {
+        Filter::Lt(name.to_string(), value.to_string())
+    }
+
+    pub fn any_of<T: ToString>(name: &str, values: &[T]) -> Self {
+        Filter::In(
+            name.to_string(),
+            values.iter().map(|v| v.to_string()).collect(),
+        )
+    }
+
+    /// Whether the field is present at all.
+    pub fn exists(name: &str, exists: bool) -> Self {
+        Filter::Exists(name.to_string(), exists)
+    }
+
+    fn write_xml(&self, xml: &mut String) {
+        match self {
+            Filter::And(filters) => write_group(xml, "AND", filters),
+            Filter::Or(filters) => write_group(xml, "OR", filters),
+            Filter::Eq(name, value) => write_operator(xml, "EQ", name, value),
+            Filter::Gt(name, value) => write_operator(xml, "GT", name, value),
+            Filter::Lt(name, value) => write_operator(xml, "LT", name, value),
+            Filter::In(name, values) => write_operator(xml, "IN", name, &values.join(",")),
+            Filter::Exists(name, exists) => {
+                write_operator(xml, "EXISTS", name, &exists.to_string())
+            }
+        }
+    }
+}
+
+fn write_group(xml: &mut String, tag: &str, filters: &[Filter]) {
+    write!(xml, "<{}>", tag).unwrap();
+    for filter in filters {
+        filter.write_xml(xml);
+    }
+    write!(xml, "</{}>", tag).unwrap();
+}
+
+fn write_operator(xml: &mut String, tag: &str, name: &str, value: &str) {
+    write!(
+        xml,
+        "<{} name='{}' value='{}' />",
+        tag,
+        escape(name),
+        escape(value)
+    )
+    .unwrap();
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&apos;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_attribute_values() {
+        let query = Query::new("TrainAnnouncement", "1.9")
+            .filter(Filter::eq("LocationSignature", "a'b<c&d\"e>f"));
+        assert_eq!(
+            query.cache_key(),
+            "<QUERY objecttype='TrainAnnouncement' schemaversion='1.9'><FILTER>\
+             <EQ name='LocationSignature' value='a&apos;b&lt;c&amp;d&quot;e&gt;f' />\
+             </FILTER></QUERY>"
+        );
+    }
+
+    #[test]
+    fn composes_nested_filters() {
+        let query = Query::new("TrainMessage", "1.7")
+            .filter(Filter::any_of(
+                "AffectedLocation.LocationSignature",
+                &["Sk", "Cst"],
+            ))
+            .and(Filter::Or(vec![
+                Filter::gt("End", "2025-01-01T12:00:00+00:00"),
+                Filter::exists("End", false),
+            ]))
+            .include(&["Header"])
+            .order_by("StartDateTime desc")
+            .last_modified();
+        assert_eq!(
+            query.cache_key(),
+            "<QUERY objecttype='TrainMessage' schemaversion='1.7' orderby='StartDateTime desc' \
+             lastmodified='true'><FILTER><AND>\
+             <IN name='AffectedLocation.LocationSignature' value='Sk,Cst' />\
+             <OR><GT name='End' value='2025-01-01T12:00:00+00:00' />\
+             <EXISTS name='End' value='false' /></OR>\
+             </AND></FILTER><INCLUDE>Header</INCLUDE></QUERY>"
+        );
+    }
+
+    #[test]
+    fn escapes_the_api_key() {
+        let xml = Request::new("k'ey").to_xml();
+        assert_eq!(
+            xml,
+            "<REQUEST><LOGIN authenticationkey='k&apos;ey' /></REQUEST>"
+        );
+    }
+}
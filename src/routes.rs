@@ -1,59 +1,440 @@
-use axum::response::Html;
+use axum::Json;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, RawQuery, State};
+use axum::http::HeaderMap;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Deserialize;
 
-use crate::api;
-use crate::models::TrainAnnouncement;
-use crate::views;
+use crate::api::{self, Fetched};
+use crate::error::AppError;
+use crate::formats::{self, Format, FormatQuery};
+use crate::ident::{Signature, TrainIdent};
+use crate::json::{self, ApiDoc, ApiError};
+use crate::locations::Position;
+use crate::models::{Activity, TrainMessage};
+use crate::search::{self, Match};
+use crate::state::AppState;
+use crate::upstream::UpstreamStatus;
+use crate::views::{self, Filters};
 
-pub async fn stations() -> axum::response::Html<String> {
-    return Html("<a href='station/Sk'>Sk</a><br/><a href='station/Tul'>Tul</a>".to_string());
+const NEARBY_LIMIT: usize = 10;
+const SEARCH_LIMIT: usize = 50;
+const AUTOCOMPLETE_LIMIT: usize = 10;
+
+/// The `/api/v1` paths, in OpenAPI syntax. Both the router and the spec take
+/// them from here, so the documented paths are the served ones.
+pub const STATION_JSON_PATH: &str = "/api/v1/station/{code}";
+pub const ARRIVALS_JSON_PATH: &str = "/api/v1/station/{code}/arrivals";
+pub const TRAIN_JSON_PATH: &str = "/api/v1/train/{id}";
+
+/// An OpenAPI path as axum 0.7 routes it, with `:name` for `{name}`.
+pub fn route_path(path: &str) -> String {
+    path.replace('{', ":").replace('}', "")
+}
+
+pub async fn stations(State(state): State<AppState>) -> Html<String> {
+    views::render_index(&state.stations.read().unwrap().all())
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+pub async fn search(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Html<String> {
+    let stations = state.stations.read().unwrap().all();
+    views::render_search(&query.q, &search::search(stations, &query.q, SEARCH_LIMIT))
+}
+
+pub async fn search_json(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Json<Vec<Match>> {
+    let stations = state.stations.read().unwrap().all();
+    Json(search::search(stations, &query.q, AUTOCOMPLETE_LIMIT))
+}
+
+#[derive(Deserialize)]
+pub struct NearbyQuery {
+    lat: f64,
+    lon: f64,
+}
+
+pub async fn nearby(
+    State(state): State<AppState>,
+    query: Result<Query<NearbyQuery>, QueryRejection>,
+) -> Result<Html<String>, AppError> {
+    let Query(query) = query?;
+    let position = Position {
+        lat: query.lat,
+        lon: query.lon,
+    };
+    let stations = state.stations.read().unwrap();
+    Ok(views::render_nearby(
+        &stations.nearest(&position, NEARBY_LIMIT),
+    ))
 }
 
 pub async fn train(
+    State(state): State<AppState>,
     axum::extract::Path(id): axum::extract::Path<String>,
-) -> axum::response::Html<String> {
-    let api_key = match std::env::var("TRAFIKVERKET_API_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            return Html("No API key found".to_string());
-        }
+    filters: Result<Query<Filters>, QueryRejection>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let format = match Format::negotiate(&format, &headers) {
+        Ok(format) => format,
+        Err(e) => return formats::vary(Format::accepted(&headers).error(e)),
+    };
+    let result = match filters {
+        Ok(Query(filters)) => train_response(&state, &id, format, &filters).await,
+        Err(rejection) => Err(rejection.into()),
     };
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => format.error(e),
+    };
+    formats::vary(response)
+}
 
-    match api::fetch_train(&api_key, &id).await {
-        Ok(api_response) => {
-            let announcements: Vec<TrainAnnouncement> = api_response
-                .response
-                .result
-                .into_iter()
-                .flat_map(|r| r.train_announcements)
-                .collect();
+async fn train_response(
+    state: &AppState,
+    id: &str,
+    format: Format,
+    filters: &Filters,
+) -> Result<Response, AppError> {
+    let (fetched, messages) = load_train(state, id).await?;
 
-            views::render_train(&announcements)
-        }
-        Err(e) => Html(format!("Error fetching data: {}", e)),
+    let stations = state.stations.read().unwrap();
+    let journey = views::journey(&stations, &fetched, filters, &state.tracks, &messages);
+    Ok(match format {
+        Format::Html => views::render_train(&journey).into_response(),
+        Format::Json => formats::json(&json::train(&journey)),
+        Format::Text => formats::text(formats::train_text(&journey)),
+        Format::Csv => formats::csv(formats::announcements_csv(&journey.calls)),
+    })
+}
+
+/// Every stop of a train running today.
+#[utoipa::path(
+    get,
+    tag = "v1",
+    path = TRAIN_JSON_PATH,
+    params(
+        ("id" = String, Path, description = "Advertised train number", example = "2616"),
+        Filters,
+    ),
+    responses(
+        (status = 200, description = "The train's stops", body = json::Train),
+        (status = 404, description = "The train is not running today", body = json::ErrorBody),
+        (status = 400, description = "Not a valid request", body = json::ErrorBody),
+        (status = 502, description = "Trafikverket failed to answer", body = json::ErrorBody),
+        (status = 503, description = "Trafikverket is unavailable", body = json::ErrorBody),
+        (status = 504, description = "Trafikverket took too long", body = json::ErrorBody),
+    )
+)]
+pub async fn train_json(
+    State(state): State<AppState>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    filters: Result<Query<Filters>, QueryRejection>,
+) -> Result<Json<json::Train>, ApiError> {
+    let Query(filters) = filters.map_err(AppError::from)?;
+    let (fetched, messages) = load_train(&state, &id).await?;
+    let stations = state.stations.read().unwrap();
+    let journey = views::journey(&stations, &fetched, &filters, &state.tracks, &messages);
+    Ok(Json(json::train(&journey)))
+}
+
+/// The train's announcements and the disruptions at its stops.
+async fn load_train(state: &AppState, id: &str) -> Result<(Fetched, Vec<TrainMessage>), AppError> {
+    let id = TrainIdent::parse(id).ok_or(AppError::InvalidTrainIdent(id.to_string()))?;
+    let fetched = api::fetch_train(state, &id).await?;
+    let announcements = fetched.announcements();
+    if announcements.is_empty() {
+        return Err(AppError::TrainNotFound(id.to_string()));
     }
+
+    let stops: Vec<&str> = announcements
+        .iter()
+        .map(|a| a.location_signature.as_str())
+        .collect();
+    let messages = active_messages(api::fetch_messages(state, &stops).await);
+    Ok((fetched, messages))
 }
 
 pub async fn station(
+    State(state): State<AppState>,
     axum::extract::Path(code): axum::extract::Path<String>,
-) -> axum::response::Html<String> {
-    let api_key = match std::env::var("TRAFIKVERKET_API_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            return Html("No API key found".to_string());
-        }
+    RawQuery(raw_query): RawQuery,
+    filters: Result<Query<Filters>, QueryRejection>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let request = filters.map(|Query(filters)| BoardRequest {
+        activity: Activity::Departure,
+        filters,
+        raw_query,
+    });
+    negotiated_board(&state, code, request, &format, &headers).await
+}
+
+pub async fn arrivals(
+    State(state): State<AppState>,
+    axum::extract::Path(code): axum::extract::Path<String>,
+    RawQuery(raw_query): RawQuery,
+    filters: Result<Query<Filters>, QueryRejection>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let request = filters.map(|Query(filters)| BoardRequest {
+        activity: Activity::Arrival,
+        filters,
+        raw_query,
+    });
+    negotiated_board(&state, code, request, &format, &headers).await
+}
+
+struct BoardRequest {
+    activity: Activity,
+    filters: Filters,
+    raw_query: Option<String>,
+}
+
+async fn negotiated_board(
+    state: &AppState,
+    code: String,
+    request: Result<BoardRequest, QueryRejection>,
+    format: &FormatQuery,
+    headers: &HeaderMap,
+) -> Response {
+    let format = match Format::negotiate(format, headers) {
+        Ok(format) => format,
+        Err(e) => return formats::vary(Format::accepted(headers).error(e)),
+    };
+    let result = match request {
+        Ok(request) => board(state, code, format, &request).await,
+        Err(rejection) => Err(rejection.into()),
+    };
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => format.error(e),
     };
+    formats::vary(response)
+}
+
+async fn board(
+    state: &AppState,
+    code: String,
+    format: Format,
+    request: &BoardRequest,
+) -> Result<Response, AppError> {
+    let BoardRequest {
+        activity,
+        filters,
+        raw_query,
+    } = request;
+    let parsed = Signature::parse(&code, &state.stations.read().unwrap());
+    let Some(code) = parsed else {
+        return station_by_name(state, &code, *activity, raw_query.as_deref());
+    };
+    let (fetched, messages) = load_board(state, &code, *activity).await?;
+
+    let stations = state.stations.read().unwrap();
+    let board = views::board(
+        &stations,
+        &code,
+        *activity,
+        &fetched,
+        filters,
+        &state.tracks,
+        &messages,
+    );
+    Ok(match format {
+        Format::Html => views::render_station(&board).into_response(),
+        Format::Json => formats::json(&json::station_board(&board)),
+        Format::Text => formats::text(formats::board_text(&board)),
+        Format::Csv => formats::csv(formats::announcements_csv(&board.calls)),
+    })
+}
+
+/// Departures at a station.
+///
+/// Announcements from a few hours ago until later today, in timetable order.
+#[utoipa::path(
+    get,
+    tag = "v1",
+    path = STATION_JSON_PATH,
+    params(
+        ("code" = String, Path, description = "Location signature, such as Cst", example = "Cst"),
+        Filters,
+    ),
+    responses(
+        (status = 200, description = "Departures", body = json::StationBoard),
+        (status = 404, description = "No station has this signature", body = json::ErrorBody),
+        (status = 400, description = "Not a valid request", body = json::ErrorBody),
+        (status = 502, description = "Trafikverket failed to answer", body = json::ErrorBody),
+        (status = 503, description = "Trafikverket is unavailable", body = json::ErrorBody),
+        (status = 504, description = "Trafikverket took too long", body = json::ErrorBody),
+    )
+)]
+pub async fn station_json(
+    State(state): State<AppState>,
+    axum::extract::Path(code): axum::extract::Path<String>,
+    filters: Result<Query<Filters>, QueryRejection>,
+) -> Result<Json<json::StationBoard>, ApiError> {
+    let Query(filters) = filters.map_err(AppError::from)?;
+    board_json(&state, code, Activity::Departure, &filters).await
+}
+
+/// Arrivals at a station.
+///
+/// Announcements from a few hours ago until later today, in timetable order.
+#[utoipa::path(
+    get,
+    tag = "v1",
+    path = ARRIVALS_JSON_PATH,
+    params(
+        ("code" = String, Path, description = "Location signature, such as Cst", example = "Cst"),
+        Filters,
+    ),
+    responses(
+        (status = 200, description = "Arrivals", body = json::StationBoard),
+        (status = 404, description = "No station has this signature", body = json::ErrorBody),
+        (status = 400, description = "Not a valid request", body = json::ErrorBody),
+        (status = 502, description = "Trafikverket failed to answer", body = json::ErrorBody),
+        (status = 503, description = "Trafikverket is unavailable", body = json::ErrorBody),
+        (status = 504, description = "Trafikverket took too long", body = json::ErrorBody),
+    )
+)]
+pub async fn arrivals_json(
+    State(state): State<AppState>,
+    axum::extract::Path(code): axum::extract::Path<String>,
+    filters: Result<Query<Filters>, QueryRejection>,
+) -> Result<Json<json::StationBoard>, ApiError> {
+    let Query(filters) = filters.map_err(AppError::from)?;
+    board_json(&state, code, Activity::Arrival, &filters).await
+}
+
+async fn board_json(
+    state: &AppState,
+    code: String,
+    activity: Activity,
+    filters: &Filters,
+) -> Result<Json<json::StationBoard>, ApiError> {
+    let parsed = Signature::parse(&code, &state.stations.read().unwrap());
+    let code = parsed.ok_or(AppError::UnknownStation(code))?;
+    let (fetched, messages) = load_board(state, &code, activity).await?;
+    let stations = state.stations.read().unwrap();
+    let board = views::board(
+        &stations,
+        &code,
+        activity,
+        &fetched,
+        filters,
+        &state.tracks,
+        &messages,
+    );
+    Ok(Json(json::station_board(&board)))
+}
+
+/// The station's announcements and the disruptions affecting it.
+async fn load_board(
+    state: &AppState,
+    code: &Signature,
+    activity: Activity,
+) -> Result<(Fetched, Vec<TrainMessage>), AppError> {
+    let signature = code.to_string();
+    let locations = [signature.as_str()];
+    let (fetched, messages) = tokio::join!(
+        api::fetch_station(state, code, activity),
+        api::fetch_messages(state, &locations),
+    );
+    Ok((fetched?, active_messages(messages)))
+}
 
-    match api::fetch_station(&api_key, &code).await {
-        Ok(api_response) => {
-            let announcements: Vec<TrainAnnouncement> = api_response
-                .response
-                .result
-                .into_iter()
-                .flat_map(|r| r.train_announcements)
+/// Lets a station name stand in for its signature: a single match redirects
+/// to the signature URL, several answer 300 Multiple Choices with a page to
+/// choose from.
+fn station_by_name(
+    state: &AppState,
+    name: &str,
+    activity: Activity,
+    raw_query: Option<&str>,
+) -> Result<Response, AppError> {
+    let stations = state.stations.read().unwrap();
+    let signatures = stations.signatures_named(name);
+    match signatures.as_slice() {
+        [] => Err(AppError::UnknownStation(name.to_string())),
+        [signature] => {
+            Ok(Redirect::temporary(&station_url(signature, activity, raw_query)).into_response())
+        }
+        _ => {
+            let choices: Vec<(String, String)> = signatures
+                .iter()
+                .map(|signature| {
+                    (
+                        signature.clone(),
+                        station_url(signature, activity, raw_query),
+                    )
+                })
                 .collect();
+            Err(AppError::AmbiguousStation(
+                name.to_string(),
+                views::station_choices(&stations, &choices),
+            ))
+        }
+    }
+}
+
+fn station_url(signature: &str, activity: Activity, raw_query: Option<&str>) -> String {
+    let mut url = format!(
+        "/station/{}",
+        utf8_percent_encode(signature, NON_ALPHANUMERIC)
+    );
+    if let Activity::Arrival = activity {
+        url.push_str("/arrivals");
+    }
+    if let Some(query) = raw_query.filter(|query| !query.is_empty()) {
+        url.push('?');
+        url.push_str(query);
+    }
+    url
+}
+
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::spec())
+}
+
+pub async fn api_docs() -> Html<String> {
+    views::render_api_docs(&ApiDoc::spec())
+}
+
+pub async fn disruptions(State(state): State<AppState>) -> Result<Html<String>, AppError> {
+    let fetched = api::fetch_all_messages(&state).await?;
+    let stations = state.stations.read().unwrap();
+    Ok(views::render_disruptions(&stations, &fetched))
+}
+
+pub async fn upstream_status(State(state): State<AppState>) -> Json<UpstreamStatus> {
+    Json(UpstreamStatus {
+        breaker: state.breaker.status(),
+        requests: state.coalescer.stats(),
+    })
+}
 
-            views::render_station(&announcements)
+/// Disruption banners are a nice-to-have, so a failure to fetch them is
+/// logged rather than failing the page.
+fn active_messages(fetched: Result<Fetched, AppError>) -> Vec<TrainMessage> {
+    match fetched {
+        Ok(fetched) => fetched.active_messages(),
+        Err(e) => {
+            eprintln!("Could not fetch train messages: {}", e);
+            Vec::new()
         }
-        Err(e) => Html(format!("Error fetching data: {}", e)),
     }
 }
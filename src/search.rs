@@ -0,0 +1,165 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Match {
+    pub signature: String,
+    pub name: String,
+    pub url: String,
+}
+
+/// The `stations`, as (signature, name) pairs, whose name or signature
+/// matches `query`, ignoring case and diacritics, best matches first: exact
+/// names and signatures, then name prefixes, word prefixes, substrings and
+/// finally words one typo away.
+pub fn search(stations: Vec<(String, String)>, query: &str, limit: usize) -> Vec<Match> {
+    let query = normalize(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let query_words: Vec<&str> = query.split(' ').collect();
+
+    let mut ranked: Vec<(u8, String, String)> = stations
+        .into_iter()
+        .filter_map(|(signature, name)| {
+            let rank = rank(
+                &query,
+                &query_words,
+                &normalize(&signature),
+                &normalize(&name),
+            )?;
+            Some((rank, signature, name))
+        })
+        .collect();
+    ranked.sort_by_cached_key(|(rank, _, name)| (*rank, name.chars().count(), name.clone()));
+
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(_, signature, name)| Match {
+            url: format!("/station/{}", signature),
+            signature,
+            name,
+        })
+        .collect()
+}
+
+fn rank(query: &str, query_words: &[&str], signature: &str, name: &str) -> Option<u8> {
+    let words: Vec<&str> = name.split(' ').collect();
+    if name == query || signature == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1)
+    } else if query_words
+        .iter()
+        .all(|q| words.iter().any(|w| w.starts_with(q)))
+    {
+        Some(2)
+    } else if name.contains(query) {
+        Some(3)
+    } else if query_words
+        .iter()
+        .all(|q| words.iter().any(|w| within_one_edit(q, w)))
+    {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// Lowercase, without diacritics, with punctuation turned into single spaces,
+/// so that "Göteborg C" and "goteborg-c" compare equal.
+pub fn normalize(text: &str) -> String {
+    let folded: String = text
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'å' | 'ä' | 'á' | 'à' | 'â' => 'a',
+            'ö' | 'ø' | 'ó' | 'ò' | 'ô' => 'o',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'ü' | 'ú' | 'ù' => 'u',
+            'í' | 'ì' | 'ï' => 'i',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Words of four letters or more may differ by one insertion, deletion or
+/// substitution.
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len() < 4 || a.len().abs_diff(b.len()) > 1 {
+        return false;
+    }
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    a.len().max(b.len()) - prefix - suffix <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stations(names: &[(&str, &str)]) -> Vec<(String, String)> {
+        names
+            .iter()
+            .map(|(signature, name)| (signature.to_string(), name.to_string()))
+            .collect()
+    }
+
+    fn names(matches: &[Match]) -> Vec<&str> {
+        matches.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn ignores_case_and_diacritics() {
+        assert_eq!(normalize("Göteborg C"), "goteborg c");
+        assert_eq!(normalize("  Södertälje-Syd "), "sodertalje syd");
+
+        let stations = stations(&[
+            ("G", "Göteborg C"),
+            ("Söc", "Södertälje C"),
+            ("Söö", "Södertälje S"),
+            ("Sk", "Skövde C"),
+        ]);
+        let matches = search(stations.clone(), "goteborg", 10);
+        assert_eq!(names(&matches), ["Göteborg C"]);
+        let matches = search(stations, "sodertalje", 10);
+        assert_eq!(names(&matches), ["Södertälje C", "Södertälje S"]);
+    }
+
+    #[test]
+    fn ranks_prefixes_then_words_then_substrings_then_typos() {
+        let stations = stations(&[
+            ("Bo", "Borg"),
+            ("Flb", "Flemingsberg"),
+            ("Öb", "Övre Bergvik"),
+            ("Bb", "Bergsbyn"),
+            ("Be", "Berg"),
+        ]);
+        let matches = search(stations, "berg", 10);
+        assert_eq!(
+            names(&matches),
+            ["Berg", "Bergsbyn", "Övre Bergvik", "Flemingsberg", "Borg"]
+        );
+    }
+
+    #[test]
+    fn one_edit_apart() {
+        assert!(within_one_edit("malmo", "malmoe"));
+        assert!(within_one_edit("malmo", "malo"));
+        assert!(within_one_edit("malmo", "malmi"));
+        assert!(within_one_edit("malmo", "malmo"));
+        assert!(!within_one_edit("malmo", "mamlo"));
+        assert!(!within_one_edit("malmo", "mal"));
+        assert!(within_one_edit("lund", "lunds"));
+        assert!(!within_one_edit("lun", "lund"), "too short to allow a typo");
+    }
+}
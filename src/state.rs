@@ -0,0 +1,34 @@
+use std::sync::{Arc, RwLock};
+
+use crate::cache::Cache;
+use crate::coalesce::Coalescer;
+use crate::config::Config;
+use crate::locations::Stations;
+use crate::tracks::TrackHistory;
+use crate::upstream::CircuitBreaker;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub client: reqwest::Client,
+    pub breaker: Arc<CircuitBreaker>,
+    pub cache: Arc<Cache>,
+    pub coalescer: Arc<Coalescer>,
+    pub tracks: Arc<TrackHistory>,
+    pub stations: Arc<RwLock<Stations>>,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Self {
+        let breaker = CircuitBreaker::new(config.breaker_threshold, config.breaker_cooldown());
+        AppState {
+            config: Arc::new(config),
+            client: reqwest::Client::new(),
+            breaker: Arc::new(breaker),
+            cache: Arc::new(Cache::default()),
+            coalescer: Arc::new(Coalescer::default()),
+            tracks: Arc::new(TrackHistory::default()),
+            stations: Arc::new(RwLock::new(Stations::default())),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::models::TrainAnnouncement;
+
+const REMEMBER: chrono::TimeDelta = chrono::Duration::hours(24);
+
+/// Train ident, location signature and advertised time.
+type Call = (String, String, DateTime<FixedOffset>);
+
+/// The first track seen for each train call, so that later changes can be
+/// pointed out. Trafikverket only reports the current track.
+#[derive(Default)]
+pub struct TrackHistory {
+    planned: Mutex<HashMap<Call, String>>,
+}
+
+impl TrackHistory {
+    /// The originally seen track, if the announcement's track differs from it.
+    pub fn changed_from(&self, announcement: &TrainAnnouncement) -> Option<String> {
+        let track = announcement.track_at_location.as_ref()?;
+        let key = (
+            announcement.advertised_train_ident.clone(),
+            announcement.location_signature.clone(),
+            announcement.advertised_time_at_location,
+        );
+        let mut planned = self.planned.lock().unwrap();
+        let first = planned.entry(key).or_insert_with(|| track.clone());
+        (first != track).then(|| first.clone())
+    }
+
+    pub fn forget_old(&self) {
+        let cutoff = Utc::now() - REMEMBER;
+        self.planned
+            .lock()
+            .unwrap()
+            .retain(|(_, _, time), _| *time > cutoff);
+    }
+}
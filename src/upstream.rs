@@ -0,0 +1,161 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::coalesce::CoalescerStats;
+
+/// Stops calling Trafikverket for `cooldown` after `threshold` consecutive
+/// failed fetches. Once the cooldown has passed a single trial request is let
+/// through; its outcome closes or re-opens the breaker.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+    last_failure: Option<(DateTime<Local>, String)>,
+}
+
+#[derive(Serialize)]
+pub struct UpstreamStatus {
+    pub breaker: BreakerStatus,
+    pub requests: CoalescerStats,
+}
+
+#[derive(Serialize)]
+pub struct BreakerStatus {
+    pub state: &'static str,
+    pub consecutive_failures: u32,
+    pub threshold: u32,
+    pub retry_in_secs: Option<u64>,
+    pub last_failure_at: Option<String>,
+    pub last_failure: Option<String>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// A permit to call Trafikverket, or `None` while the breaker is open.
+    pub fn allow(&self) -> Option<Permit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            None => Some(Permit {
+                breaker: self,
+                trial: false,
+            }),
+            Some(until) if Instant::now() < until => None,
+            Some(_) if state.trial_in_flight => None,
+            Some(_) => {
+                state.trial_in_flight = true;
+                Some(Permit {
+                    breaker: self,
+                    trial: true,
+                })
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.trial_in_flight = false;
+    }
+
+    fn record_failure(&self, error: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        state.trial_in_flight = false;
+        state.last_failure = Some((Local::now(), error.to_string()));
+        if state.consecutive_failures >= self.threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let (name, retry_in) = match state.open_until {
+            None => ("closed", None),
+            Some(until) if now < until => ("open", Some((until - now).as_secs())),
+            Some(_) => ("half-open", None),
+        };
+        BreakerStatus {
+            state: name,
+            consecutive_failures: state.consecutive_failures,
+            threshold: self.threshold,
+            retry_in_secs: retry_in,
+            last_failure_at: state
+                .last_failure
+                .as_ref()
+                .map(|(at, _)| at.format("%Y-%m-%d %H:%M:%S").to_string()),
+            last_failure: state.last_failure.as_ref().map(|(_, e)| e.clone()),
+        }
+    }
+}
+
+/// Reports the outcome of a request the breaker let through. A permit
+/// dropped without an outcome, because the request was cancelled, frees the
+/// half-open trial for the next request instead of leaving it taken forever.
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    trial: bool,
+}
+
+impl Permit<'_> {
+    pub fn success(mut self) {
+        self.trial = false;
+        self.breaker.record_success();
+    }
+
+    pub fn failure(mut self, error: &str) {
+        self.trial = false;
+        self.breaker.record_failure(error);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.trial {
+            self.breaker.state.lock().unwrap().trial_in_flight = false;
+        }
+    }
+}
+
+/// Exponential backoff with ±50 % jitter so retries from concurrent
+/// requests do not arrive in lockstep.
+pub fn backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(1 << attempt.min(6))
+        .mul_f64(0.5 + fastrand::f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abandoned_trial_lets_the_next_request_try() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.allow().unwrap().failure("down");
+
+        let trial = breaker.allow().unwrap();
+        assert!(breaker.allow().is_none());
+        drop(trial);
+
+        breaker.allow().unwrap().success();
+        assert_eq!(breaker.status().state, "closed");
+    }
+}
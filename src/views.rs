@@ -1,12 +1,29 @@
-use crate::locations::name;
-use crate::models::{TrainAnnouncement, TrainLocation};
+use crate::api::Fetched;
+use crate::counties;
+use crate::ident::Signature;
+use crate::locations::{Station, Stations};
+use crate::models::{Activity, Notice, TrainAnnouncement, TrainLocation, TrainMessage};
+use crate::search::Match;
+use crate::tracks::TrackHistory;
 use askama::Template;
+use axum::http::StatusCode;
 use axum::response::Html;
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use utoipa::IntoParams;
 
 #[derive(Template)]
 #[template(path = "station.html")]
 struct StationTemplate {
+    signature: String,
     location_name: String,
+    arrivals: bool,
+    filters: Filters,
+    as_of: Option<String>,
+    last_modified: Option<String>,
+    messages: Vec<MessageView>,
     announcements: Vec<AnnouncementView>,
 }
 
@@ -18,38 +35,347 @@ struct TrainTemplate {
     via: String,
     destination: String,
     product_information: String,
+    filters: Filters,
+    as_of: Option<String>,
+    last_modified: Option<String>,
+    messages: Vec<MessageView>,
     announcements: Vec<AnnouncementView>,
 }
 
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    status: String,
+    message: String,
+}
+
 struct AnnouncementView {
     advertised_train_ident: String,
     advertised_time: String,
+    estimated_time: String,
     actual_time: String,
+    delay: String,
+    delay_class: &'static str,
     destination: String,
     from: String,
     location_signature: String,
     location_name: String,
     product_information: String,
+    canceled: bool,
+    track: String,
+    planned_track: Option<String>,
+    tags: Vec<Tag>,
 }
 
-pub fn render_station(announcements: &Vec<TrainAnnouncement>) -> Html<String> {
-    let announcement_views: Vec<AnnouncementView> = announcements
-        .into_iter()
-        .map(|announcement| AnnouncementView {
-            advertised_train_ident: announcement.advertised_train_ident.clone(),
-            location_signature: announcement.location_signature.clone(),
-            advertised_time: advertised_time(&announcement),
-            actual_time: actual_time(&announcement),
-            from: origin(&announcement),
-            destination: destination(&announcement),
-            location_name: name(&announcement.location_signature),
-            product_information: product_information(announcement),
-        })
-        .collect();
+struct Tag {
+    text: String,
+    kind: &'static str,
+}
+
+struct MessageView {
+    header: String,
+    description: String,
+    start: String,
+    expected_end: String,
+    stations: Vec<StationLink>,
+}
+
+#[derive(Clone)]
+pub struct StationLink {
+    pub signature: String,
+    pub name: String,
+}
+
+/// Departures or arrivals at one station: what every format of the station
+/// page is rendered from.
+pub struct Board {
+    pub station: StationLink,
+    pub activity: Activity,
+    pub filters: Filters,
+    /// When Trafikverket last changed the data.
+    pub last_modified: Option<DateTime<FixedOffset>>,
+    /// When the data was fetched, if Trafikverket is not answering and it is
+    /// served from the cache.
+    pub stale_as_of: Option<DateTime<Local>>,
+    pub disruptions: Vec<Disruption>,
+    pub calls: Vec<Call>,
+}
+
+/// Every stop of one train: what every format of the train page is rendered
+/// from.
+pub struct Journey {
+    pub train_ident: String,
+    pub product: Option<String>,
+    pub from: Vec<StationLink>,
+    pub via: Vec<StationLink>,
+    pub to: Vec<StationLink>,
+    pub filters: Filters,
+    pub last_modified: Option<DateTime<FixedOffset>>,
+    pub stale_as_of: Option<DateTime<Local>>,
+    pub disruptions: Vec<Disruption>,
+    pub calls: Vec<Call>,
+}
+
+/// One train calling at one station.
+pub struct Call {
+    pub train_ident: String,
+    pub product: Option<String>,
+    pub location: StationLink,
+    pub from: Vec<StationLink>,
+    pub via: Vec<StationLink>,
+    pub to: Vec<StationLink>,
+    pub advertised_time: DateTime<FixedOffset>,
+    pub estimated_time: Option<DateTime<FixedOffset>>,
+    pub actual_time: Option<DateTime<FixedOffset>>,
+    /// Whole minutes between the advertised time and the actual time, or
+    /// the estimated time if the train has not yet arrived or departed.
+    pub delay: Option<i64>,
+    pub canceled: bool,
+    pub track: Option<String>,
+    /// The track the train was announced at before it changed.
+    pub planned_track: Option<String>,
+    pub deviations: Vec<Notice>,
+    pub other_information: Vec<Notice>,
+    pub booking: Vec<Notice>,
+}
+
+pub struct Disruption {
+    pub header: String,
+    pub description: String,
+    pub start: DateTime<FixedOffset>,
+    pub expected_end: Option<DateTime<FixedOffset>>,
+    pub last_updated: Option<DateTime<FixedOffset>>,
+    pub stations: Vec<StationLink>,
+}
+
+#[derive(Template)]
+#[template(path = "disruptions.html")]
+struct DisruptionsTemplate {
+    as_of: Option<String>,
+    count: usize,
+    counties: Vec<CountyView>,
+}
+
+#[derive(Template)]
+#[template(path = "nearby.html")]
+struct NearbyTemplate {
+    stations: Vec<NearbyView>,
+}
+
+#[derive(Template)]
+#[template(path = "index.html")]
+struct IndexTemplate {
+    groups: Vec<LetterGroup>,
+}
+
+struct LetterGroup {
+    letter: String,
+    stations: Vec<StationLink>,
+}
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    query: String,
+    matches: Vec<Match>,
+}
+
+#[derive(Template)]
+#[template(path = "station_choices.html")]
+struct StationChoicesTemplate {
+    name: String,
+    stations: Vec<StationChoice>,
+}
+
+/// One of several stations sharing a name, with the URL of its page.
+#[derive(Debug, Clone)]
+pub struct StationChoice {
+    pub signature: String,
+    pub name: String,
+    pub county: String,
+    pub url: String,
+}
+
+#[derive(Template)]
+#[template(path = "api_docs.html")]
+struct ApiDocsTemplate {
+    title: String,
+    description: String,
+    version: String,
+    endpoints: Vec<EndpointView>,
+    schemas: Vec<SchemaView>,
+}
+
+struct EndpointView {
+    method: String,
+    path: String,
+    summary: String,
+    description: String,
+    parameters: Vec<FieldView>,
+    responses: Vec<ResponseView>,
+}
+
+struct ResponseView {
+    status: String,
+    description: String,
+    schema: Option<String>,
+}
+
+struct SchemaView {
+    name: String,
+    description: String,
+    fields: Vec<FieldView>,
+}
+
+/// A schema property or an endpoint parameter. `location` is only set for
+/// parameters and `link` names the schema `kind` refers to, if any.
+struct FieldView {
+    name: String,
+    location: String,
+    kind: String,
+    link: Option<String>,
+    required: bool,
+    description: String,
+}
+
+struct NearbyView {
+    signature: String,
+    name: String,
+    distance: String,
+}
+
+struct CountyView {
+    name: String,
+    messages: Vec<MessageView>,
+}
+
+/// Narrows what the station and train pages show, set from the query string.
+#[derive(Debug, Default, Clone, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct Filters {
+    /// Leave out cancelled trains.
+    pub hide_canceled: bool,
+    /// Only show trains with a deviation, such as a delay or a short train.
+    pub deviations_only: bool,
+}
+
+impl Filters {
+    pub fn keeps(&self, announcement: &TrainAnnouncement) -> bool {
+        let hidden_canceled = self.hide_canceled && announcement.canceled;
+        let hidden_regular = self.deviations_only && announcement.deviation.is_empty();
+        !hidden_canceled && !hidden_regular
+    }
+
+    fn query(&self) -> String {
+        let mut params = Vec::new();
+        if self.hide_canceled {
+            params.push("hide_canceled=true");
+        }
+        if self.deviations_only {
+            params.push("deviations_only=true");
+        }
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", params.join("&"))
+        }
+    }
+
+    fn toggle_canceled(&self) -> String {
+        Filters {
+            hide_canceled: !self.hide_canceled,
+            ..self.clone()
+        }
+        .query()
+    }
+
+    fn toggle_deviations(&self) -> String {
+        Filters {
+            deviations_only: !self.deviations_only,
+            ..self.clone()
+        }
+        .query()
+    }
+}
 
+pub fn board(
+    stations: &Stations,
+    code: &Signature,
+    activity: Activity,
+    fetched: &Fetched,
+    filters: &Filters,
+    tracks: &TrackHistory,
+    messages: &[TrainMessage],
+) -> Board {
+    Board {
+        station: station_link(stations, &code.to_string()),
+        activity,
+        filters: filters.clone(),
+        last_modified: fetched.response.response.last_modified(),
+        stale_as_of: fetched.as_of,
+        disruptions: messages
+            .iter()
+            .map(|message| disruption(stations, message))
+            .collect(),
+        calls: calls(stations, fetched, filters, tracks),
+    }
+}
+
+pub fn journey(
+    stations: &Stations,
+    fetched: &Fetched,
+    filters: &Filters,
+    tracks: &TrackHistory,
+    messages: &[TrainMessage],
+) -> Journey {
+    let calls = calls(stations, fetched, filters, tracks);
+    // The first stop names the train even when the filters hide it.
+    let first = fetched
+        .announcements()
+        .first()
+        .map(|announcement| call(stations, announcement, tracks));
+    let (train_ident, product, from, via, to) = match first {
+        Some(first) => (
+            first.train_ident,
+            first.product,
+            first.from,
+            first.via,
+            first.to,
+        ),
+        None => (String::new(), None, Vec::new(), Vec::new(), Vec::new()),
+    };
+    Journey {
+        train_ident,
+        product,
+        from,
+        via,
+        to,
+        filters: filters.clone(),
+        last_modified: fetched.response.response.last_modified(),
+        stale_as_of: fetched.as_of,
+        disruptions: messages
+            .iter()
+            .map(|message| disruption(stations, message))
+            .collect(),
+        calls,
+    }
+}
+
+pub fn render_station(board: &Board) -> Html<String> {
     let template = StationTemplate {
-        location_name: location(announcements),
-        announcements: announcement_views,
+        signature: board.station.signature.clone(),
+        location_name: board.station.name.clone(),
+        arrivals: board.activity == Activity::Arrival,
+        filters: board.filters.clone(),
+        as_of: board
+            .stale_as_of
+            .map(|time| time.format("%H:%M").to_string()),
+        last_modified: board
+            .last_modified
+            .map(|time| time.format("%H:%M:%S").to_string()),
+        messages: board.disruptions.iter().map(message_view).collect(),
+        announcements: board.calls.iter().map(announcement_view).collect(),
     };
 
     Html(
@@ -59,28 +385,133 @@ pub fn render_station(announcements: &Vec<TrainAnnouncement>) -> Html<String> {
     )
 }
 
-pub fn render_train(announcements: &Vec<TrainAnnouncement>) -> Html<String> {
-    let announcement_views: Vec<AnnouncementView> = announcements
-        .into_iter()
-        .map(|announcement| AnnouncementView {
-            advertised_train_ident: announcement.advertised_train_ident.clone(),
-            location_signature: announcement.location_signature.clone(),
-            advertised_time: advertised_time(&announcement),
-            actual_time: actual_time(&announcement),
-            from: origin(&announcement),
-            destination: destination(&announcement),
-            location_name: name(&announcement.location_signature),
-            product_information: product_information(announcement),
+pub fn render_train(journey: &Journey) -> Html<String> {
+    let template = TrainTemplate {
+        id: journey.train_ident.clone(),
+        from: names(&journey.from),
+        via: names(&journey.via),
+        destination: names(&journey.to),
+        product_information: journey.product.clone().unwrap_or_default(),
+        filters: journey.filters.clone(),
+        as_of: journey
+            .stale_as_of
+            .map(|time| time.format("%H:%M").to_string()),
+        last_modified: journey
+            .last_modified
+            .map(|time| time.format("%H:%M:%S").to_string()),
+        messages: journey.disruptions.iter().map(message_view).collect(),
+        announcements: journey.calls.iter().map(announcement_view).collect(),
+    };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
+}
+
+pub fn render_disruptions(stations: &Stations, fetched: &Fetched) -> Html<String> {
+    let messages = fetched.active_messages();
+    let mut by_county: BTreeMap<i32, Vec<MessageView>> = BTreeMap::new();
+    for message in &messages {
+        let counties = if message.county_no.is_empty() {
+            vec![0]
+        } else {
+            message.county_no.clone()
+        };
+        for county in counties {
+            by_county
+                .entry(normalize_county(county))
+                .or_default()
+                .push(message_view(&disruption(stations, message)));
+        }
+    }
+
+    let template = DisruptionsTemplate {
+        as_of: fetched.as_of.map(|time| time.format("%H:%M").to_string()),
+        count: messages.len(),
+        counties: by_county
+            .into_iter()
+            .map(|(county, messages)| CountyView {
+                name: counties::name(county).unwrap_or("Övriga").to_string(),
+                messages,
+            })
+            .collect(),
+    };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
+}
+
+/// Trafikverket reports Stockholm as both 1 and 2.
+fn normalize_county(county: i32) -> i32 {
+    if county == 2 { 1 } else { county }
+}
+
+pub fn render_nearby(stations: &[(Station, f64)]) -> Html<String> {
+    let template = NearbyTemplate {
+        stations: stations
+            .iter()
+            .map(|(station, distance)| NearbyView {
+                signature: station.signature.clone(),
+                name: station.name.clone(),
+                distance: format!("{:.1} km", distance),
+            })
+            .collect(),
+    };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
+}
+
+/// `stations` are (signature, name) pairs, listed under the first letter of
+/// their name in Swedish alphabetical order.
+pub fn render_index(stations: &[(String, String)]) -> Html<String> {
+    let mut stations = stations.to_vec();
+    stations.sort_by_cached_key(|(signature, name)| (swedish_order(name), signature.clone()));
+
+    let mut groups: Vec<LetterGroup> = Vec::new();
+    for (signature, name) in stations {
+        let letter: String = name.chars().take(1).flat_map(char::to_uppercase).collect();
+        if groups.last().is_none_or(|group| group.letter != letter) {
+            groups.push(LetterGroup {
+                letter: letter.clone(),
+                stations: Vec::new(),
+            });
+        }
+        if let Some(group) = groups.last_mut() {
+            group.stations.push(StationLink { signature, name });
+        }
+    }
+
+    let template = IndexTemplate { groups };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
+}
+
+/// Sorts Å, Ä and Ö after Z, as a Swedish reader expects.
+fn swedish_order(name: &str) -> Vec<u32> {
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'å' => 'z' as u32 + 1,
+            'ä' => 'z' as u32 + 2,
+            'ö' => 'z' as u32 + 3,
+            c => c as u32,
         })
-        .collect();
+        .collect()
+}
 
-    let template = TrainTemplate {
-        id: train_ident(announcements),
-        from: from(announcements),
-        via: via_stations(announcements),
-        destination: dest(announcements),
-        product_information: prod(announcements),
-        announcements: announcement_views,
+pub fn render_search(query: &str, matches: &[Match]) -> Html<String> {
+    let template = SearchTemplate {
+        query: query.to_string(),
+        matches: matches.to_vec(),
     };
     Html(
         template
@@ -89,86 +520,353 @@ pub fn render_train(announcements: &Vec<TrainAnnouncement>) -> Html<String> {
     )
 }
 
-fn advertised_time(announcement: &TrainAnnouncement) -> String {
-    announcement
-        .advertised_time_at_location
-        .format("%H:%M")
-        .to_string()
+/// `choices` pairs each signature with the URL to continue to.
+pub fn station_choices(stations: &Stations, choices: &[(String, String)]) -> Vec<StationChoice> {
+    choices
+        .iter()
+        .map(|(signature, url)| StationChoice {
+            signature: signature.clone(),
+            name: stations.name(signature),
+            county: stations
+                .lookup(signature)
+                .and_then(|station| station.county_no.first().copied())
+                .and_then(counties::name)
+                .unwrap_or_default()
+                .to_string(),
+            url: url.clone(),
+        })
+        .collect()
 }
 
-fn actual_time(announcement: &TrainAnnouncement) -> String {
-    announcement
-        .time_at_location_with_seconds
-        .map_or("".to_string(), |time| time.format("%H:%M:%S").to_string())
+pub fn render_station_choices(name: &str, choices: &[StationChoice]) -> Html<String> {
+    let template = StationChoicesTemplate {
+        name: name.to_string(),
+        stations: choices.to_vec(),
+    };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
 }
 
-fn origin(announcement: &TrainAnnouncement) -> String {
-    join_locations(&announcement.from_location)
+pub fn render_error(status: StatusCode, message: &str) -> Html<String> {
+    let template = ErrorTemplate {
+        status: status.to_string(),
+        message: message.to_string(),
+    };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
 }
 
-fn via(announcement: &TrainAnnouncement) -> String {
-    join_locations(&announcement.via_to_location)
+/// Describes the JSON API from the same OpenAPI document served at
+/// `/api/openapi.json`, so the page documents exactly what the handlers do.
+pub fn render_api_docs(spec: &utoipa::openapi::OpenApi) -> Html<String> {
+    let spec = serde_json::to_value(spec).unwrap_or_default();
+    let text = |value: &Value, key: &str| value[key].as_str().unwrap_or_default().to_string();
+    let schemas = spec["components"]["schemas"].as_object();
+
+    let mut endpoints = Vec::new();
+    for (path, operations) in spec["paths"].as_object().into_iter().flatten() {
+        for (method, operation) in operations.as_object().into_iter().flatten() {
+            endpoints.push(EndpointView {
+                method: method.to_uppercase(),
+                path: path.clone(),
+                summary: text(operation, "summary"),
+                description: text(operation, "description"),
+                parameters: operation["parameters"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|parameter| FieldView {
+                        name: text(parameter, "name"),
+                        location: text(parameter, "in"),
+                        kind: schema_kind(&parameter["schema"]),
+                        link: None,
+                        required: parameter["required"].as_bool().unwrap_or(false),
+                        description: text(parameter, "description"),
+                    })
+                    .collect(),
+                responses: operation["responses"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(status, response)| ResponseView {
+                        status: status.clone(),
+                        description: text(response, "description"),
+                        schema: schema_link(&response["content"]["application/json"]["schema"]),
+                    })
+                    .collect(),
+            });
+        }
+    }
+
+    let template = ApiDocsTemplate {
+        title: text(&spec["info"], "title"),
+        description: text(&spec["info"], "description"),
+        version: text(&spec["info"], "version"),
+        endpoints,
+        schemas: schemas
+            .into_iter()
+            .flatten()
+            .map(|(name, schema)| SchemaView {
+                name: name.clone(),
+                description: text(schema, "description"),
+                fields: schema_fields(schema, schemas),
+            })
+            .collect(),
+    };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
 }
 
-fn destination(announcement: &TrainAnnouncement) -> String {
-    join_locations(&announcement.to_location)
+/// The properties of an object schema, including those of schemas it is
+/// composed from with `allOf`.
+fn schema_fields(
+    schema: &Value,
+    schemas: Option<&serde_json::Map<String, Value>>,
+) -> Vec<FieldView> {
+    if let Some(parts) = schema["allOf"].as_array() {
+        return parts
+            .iter()
+            .flat_map(|part| match schema_link(part) {
+                Some(name) => schemas
+                    .and_then(|schemas| schemas.get(&name))
+                    .map_or(Vec::new(), |schema| schema_fields(schema, schemas)),
+                None => schema_fields(part, schemas),
+            })
+            .collect();
+    }
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    schema["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, property)| FieldView {
+            name: name.clone(),
+            location: String::new(),
+            kind: schema_kind(property),
+            link: schema_link(property),
+            required: required.contains(&name.as_str()),
+            description: property["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        })
+        .collect()
 }
 
-fn join_locations(locations: &Vec<TrainLocation>) -> String {
-    locations
+/// A short description of a schema's type, such as `string (date-time) or
+/// null` or `array of Location`.
+fn schema_kind(schema: &Value) -> String {
+    if let Some(name) = schema["$ref"].as_str() {
+        return name.rsplit('/').next().unwrap_or(name).to_string();
+    }
+    if let Some(alternatives) = schema["oneOf"].as_array() {
+        return alternatives
+            .iter()
+            .map(schema_kind)
+            .collect::<Vec<String>>()
+            .join(" or ");
+    }
+    let types: Vec<String> = match &schema["type"] {
+        Value::String(kind) => vec![kind.clone()],
+        Value::Array(kinds) => kinds
+            .iter()
+            .filter_map(|kind| kind.as_str().map(str::to_string))
+            .collect(),
+        _ => vec!["any".to_string()],
+    };
+    types
         .iter()
-        .map(|loc| name(&loc.location_name))
+        .map(|kind| match (kind.as_str(), schema["format"].as_str()) {
+            ("array", _) => format!("array of {}", schema_kind(&schema["items"])),
+            ("null", _) => "null".to_string(),
+            (kind, Some(format)) => format!("{} ({})", kind, format),
+            (kind, None) => kind.to_string(),
+        })
         .collect::<Vec<String>>()
-        .join(", ")
+        .join(" or ")
 }
 
-fn product_information(announcement: &TrainAnnouncement) -> String {
-    announcement
-        .product_information
-        .first()
-        .map_or("".to_string(), |product| product.description.clone())
+/// The name of the component schema `schema` refers to, directly or as the
+/// items of an array or one of several alternatives.
+fn schema_link(schema: &Value) -> Option<String> {
+    if !schema.is_object() {
+        return None;
+    }
+    if let Some(name) = schema["$ref"].as_str() {
+        return name.rsplit('/').next().map(str::to_string);
+    }
+    schema_link(&schema["items"]).or_else(|| {
+        schema["oneOf"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find_map(schema_link)
+    })
+}
+
+/// The announcements `filters` keeps, in timetable order.
+fn calls(
+    stations: &Stations,
+    fetched: &Fetched,
+    filters: &Filters,
+    tracks: &TrackHistory,
+) -> Vec<Call> {
+    tracks.forget_old();
+    fetched
+        .announcements()
+        .iter()
+        .filter(|announcement| filters.keeps(announcement))
+        .map(|announcement| call(stations, announcement, tracks))
+        .collect()
 }
 
-fn location(announcements: &Vec<TrainAnnouncement>) -> String {
-    name(
-        announcements
+fn call(stations: &Stations, announcement: &TrainAnnouncement, tracks: &TrackHistory) -> Call {
+    Call {
+        train_ident: announcement.advertised_train_ident.clone(),
+        product: announcement
+            .product_information
             .first()
-            .map(|a| a.location_signature.as_str())
-            .unwrap_or("Unknown"),
-    )
+            .map(|product| product.description.clone()),
+        location: station_link(stations, &announcement.location_signature),
+        from: station_links(stations, &announcement.from_location),
+        via: station_links(stations, &announcement.via_to_location),
+        to: station_links(stations, &announcement.to_location),
+        advertised_time: announcement.advertised_time_at_location,
+        estimated_time: announcement.estimated_time_at_location,
+        actual_time: announcement.time_at_location_with_seconds,
+        delay: announcement
+            .time_at_location_with_seconds
+            .or(announcement.estimated_time_at_location)
+            .map(|time| (time - announcement.advertised_time_at_location).num_minutes()),
+        canceled: announcement.canceled,
+        track: announcement.track_at_location.clone(),
+        planned_track: tracks.changed_from(announcement),
+        deviations: announcement.deviation.clone(),
+        other_information: announcement.other_information.clone(),
+        booking: announcement.booking.clone(),
+    }
 }
 
-fn train_ident(announcements: &Vec<TrainAnnouncement>) -> String {
-    announcements
-        .first()
-        .map(|a| a.advertised_train_ident.clone())
-        .unwrap_or("Unknown".to_string())
+fn station_link(stations: &Stations, signature: &str) -> StationLink {
+    StationLink {
+        signature: signature.to_string(),
+        name: stations.name(signature),
+    }
 }
 
-fn from(announcements: &Vec<TrainAnnouncement>) -> String {
-    announcements
-        .first()
-        .map(origin)
-        .unwrap_or("Unknown".to_string())
+fn station_links(stations: &Stations, locations: &[TrainLocation]) -> Vec<StationLink> {
+    locations
+        .iter()
+        .map(|location| station_link(stations, &location.location_name))
+        .collect()
 }
 
-fn via_stations(announcements: &Vec<TrainAnnouncement>) -> String {
-    announcements
-        .first()
-        .map(via)
-        .unwrap_or("Unknown".to_string())
+pub fn names(stations: &[StationLink]) -> String {
+    stations
+        .iter()
+        .map(|station| station.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ")
 }
 
-fn dest(announcements: &Vec<TrainAnnouncement>) -> String {
-    announcements
-        .first()
-        .map(destination)
-        .unwrap_or("Unknown".to_string())
+fn announcement_view(call: &Call) -> AnnouncementView {
+    AnnouncementView {
+        advertised_train_ident: call.train_ident.clone(),
+        location_signature: call.location.signature.clone(),
+        advertised_time: call.advertised_time.format("%H:%M").to_string(),
+        estimated_time: call
+            .estimated_time
+            .map_or("".to_string(), |time| time.format("%H:%M").to_string()),
+        actual_time: call
+            .actual_time
+            .map_or("".to_string(), |time| time.format("%H:%M:%S").to_string()),
+        delay: call
+            .delay
+            .map_or("".to_string(), |minutes| format!("{:+}", minutes)),
+        delay_class: delay_class(call.delay),
+        from: names(&call.from),
+        destination: names(&call.to),
+        location_name: call.location.name.clone(),
+        product_information: call.product.clone().unwrap_or_default(),
+        canceled: call.canceled,
+        track: call.track.clone().unwrap_or_default(),
+        planned_track: call.planned_track.clone(),
+        tags: tags(call),
+    }
 }
 
-fn prod(announcements: &Vec<TrainAnnouncement>) -> String {
-    announcements
-        .first()
-        .map(product_information)
-        .unwrap_or("Unknown".to_string())
+fn disruption(stations: &Stations, message: &TrainMessage) -> Disruption {
+    Disruption {
+        header: message
+            .reason_code_text
+            .clone()
+            .unwrap_or_else(|| message.header.clone()),
+        description: message.external_description.clone(),
+        start: message.start_date_time,
+        expected_end: message.prognosticated_end_date_time_traffic_impact,
+        last_updated: message.last_update_date_time,
+        stations: message
+            .affected_location
+            .iter()
+            .map(|location| station_link(stations, &location.location_signature))
+            .collect(),
+    }
+}
+
+fn message_view(disruption: &Disruption) -> MessageView {
+    MessageView {
+        header: disruption.header.clone(),
+        description: disruption.description.clone(),
+        start: message_time(disruption.start),
+        expected_end: disruption.expected_end.map_or("".to_string(), message_time),
+        stations: disruption.stations.clone(),
+    }
+}
+
+/// Disruptions can last for days, so their times carry the date unless it
+/// is today.
+fn message_time(time: DateTime<FixedOffset>) -> String {
+    let today = Local::now().with_timezone(time.offset()).date_naive();
+    if time.date_naive() == today {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%Y-%m-%d %H:%M").to_string()
+    }
+}
+
+fn delay_class(delay: Option<i64>) -> &'static str {
+    match delay {
+        None => "",
+        Some(..=0) => "delay on-time",
+        Some(1..=4) => "delay late",
+        Some(_) => "delay very-late",
+    }
+}
+
+fn tags(call: &Call) -> Vec<Tag> {
+    let deviation = call.deviations.iter().map(|n| (n, "deviation"));
+    let other = call.other_information.iter().map(|n| (n, "other"));
+    let booking = call.booking.iter().map(|n| (n, "booking"));
+    deviation
+        .chain(other)
+        .chain(booking)
+        .map(|(notice, kind)| Tag {
+            text: notice.description.clone(),
+            kind,
+        })
+        .collect()
 }
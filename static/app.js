@@ -0,0 +1,61 @@
+// Small enhancements shared by every page; each part only runs when its
+// elements are present.
+
+const load = (list) => JSON.parse(localStorage.getItem(list) || "[]");
+
+for (const input of document.querySelectorAll("input[list]")) {
+    const matches = document.getElementById(input.getAttribute("list"));
+    input.addEventListener("input", () =>
+        fetch("/search.json?q=" + encodeURIComponent(input.value))
+            .then((response) => response.json())
+            .then((found) =>
+                matches.replaceChildren(
+                    ...found.map((m) => Object.assign(document.createElement("option"), { value: m.name })),
+                ),
+            ),
+    );
+}
+
+document.getElementById("nearby")?.addEventListener("click", () =>
+    navigator.geolocation.getCurrentPosition(
+        (p) => (location.href = "/nearby?lat=" + p.coords.latitude + "&lon=" + p.coords.longitude),
+    ),
+);
+
+// Favorites and recently viewed stations on the index page.
+for (const list of ["favorites", "recent"]) {
+    const section = document.getElementById(list);
+    if (!section) {
+        continue;
+    }
+    const stations = load(list);
+    section.hidden = stations.length === 0;
+    section.querySelector("ul").replaceChildren(
+        ...stations.map((station) => {
+            const item = document.createElement("li");
+            item.append(
+                Object.assign(document.createElement("a"), {
+                    href: "/station/" + encodeURIComponent(station.signature),
+                    textContent: station.name,
+                }),
+            );
+            return item;
+        }),
+    );
+}
+
+// The favorite button on station pages, which also records the visit.
+const favorite = document.getElementById("favorite");
+if (favorite) {
+    const station = { signature: favorite.dataset.signature, name: favorite.dataset.name };
+    const others = (list) => load(list).filter((s) => s.signature !== station.signature);
+    const isFavorite = () => load("favorites").some((s) => s.signature === station.signature);
+    const show = () => (favorite.textContent = (isFavorite() ? "★" : "☆") + " Favorite");
+    localStorage.setItem("recent", JSON.stringify([station, ...others("recent")].slice(0, 10)));
+    favorite.addEventListener("click", () => {
+        const favorites = isFavorite() ? others("favorites") : [...load("favorites"), station];
+        localStorage.setItem("favorites", JSON.stringify(favorites));
+        show();
+    });
+    show();
+}